@@ -0,0 +1,100 @@
+use crate::error::AssemblerError;
+use crate::object_file::ObjectFile;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// First line of every archive file, followed by the format version
+pub const ARCHIVE_HEADER: &str = "CRLIB 1";
+
+/// A library of object files, the linker only uses the members that define symbols the program needs
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Archive {
+    /// Name of the archive, used in linker messages
+    pub name: String,
+    pub members: Vec<ObjectFile>,
+}
+
+impl Archive {
+    /// Convert the archive into its text file format, each member is stored as its object text after a line giving its line count
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{ARCHIVE_HEADER}\n");
+        for member in &self.members {
+            let member_text = member.to_text();
+            text += &format!("member {}\n{member_text}", member_text.lines().count());
+        }
+        text
+    }
+
+    /// Read an archive from its text file format
+    pub fn from_text(name: &str, text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(ARCHIVE_HEADER) {
+            return Err("not a cr_cpu archive file".to_string());
+        }
+
+        let mut archive = Archive {
+            name: name.to_string(),
+            members: vec![],
+        };
+        while let Some(line) = lines.next() {
+            let line_count: usize = line
+                .strip_prefix("member ")
+                .and_then(|count| count.parse().ok())
+                .ok_or_else(|| format!("unexpected line in archive: {line}"))?;
+            let member_text = lines.by_ref().take(line_count).collect::<Vec<&str>>().join("\n");
+            archive.members.push(ObjectFile::from_text(&member_text)?);
+        }
+        Ok(archive)
+    }
+
+    /// Write the archive to a file
+    pub fn write(&self, path: &Path) -> Result<(), AssemblerError> {
+        fs::write(path, self.to_text())
+            .map_err(|err| AssemblerError::without_location(format!("unable to write '{}': {err}", path.display())))
+    }
+
+    /// Read an archive from a file
+    pub fn read(path: &Path) -> Result<Self, AssemblerError> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| AssemblerError::without_location(format!("unable to read '{}': {err}", path.display())))?;
+        Self::from_text(&path.display().to_string(), &text)
+            .map_err(|msg| AssemblerError::without_location(format!("{}: {msg}", path.display())))
+    }
+}
+
+/// Pick the archive members that are needed to link the given objects.
+/// A member is used if it exports a symbol that is used but not yet defined, which is repeated until no more members are needed,
+/// so members can depend on other members. Used members are renamed to `archive(member)` for linker messages
+pub fn select_members(objects: &[ObjectFile], archives: &[Archive]) -> Vec<ObjectFile> {
+    let mut selected: Vec<ObjectFile> = vec![];
+    let mut used: BTreeSet<(usize, usize)> = BTreeSet::new();
+
+    loop {
+        let all = || objects.iter().chain(selected.iter());
+        let exported: BTreeSet<&str> = all().flat_map(|object| object.exports()).map(|symbol| symbol.name.as_str()).collect();
+        let undefined: BTreeSet<&str> = all()
+            .flat_map(|object| object.imports())
+            .filter(|name| !exported.contains(name))
+            .collect();
+
+        let found = archives.iter().enumerate().find_map(|(archive_index, archive)| {
+            archive.members.iter().enumerate().find_map(|(member_index, member)| {
+                let needed = !used.contains(&(archive_index, member_index))
+                    && member.exports().any(|symbol| undefined.contains(symbol.name.as_str()));
+                needed.then_some((archive_index, member_index))
+            })
+        });
+
+        match found {
+            Some((archive_index, member_index)) => {
+                used.insert((archive_index, member_index));
+                let archive = &archives[archive_index];
+                let mut member = archive.members[member_index].clone();
+                member.name = format!("{}({})", archive.name, member.name);
+                selected.push(member);
+            }
+            None => return selected,
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::directive::parse_word;
+use crate::profile::Profile;
+use crate::program_file::AssemblerOptions;
+use std::path::PathBuf;
+
+/// Exit code for assembly, link and file errors
+pub const EXIT_ERROR: i32 = 1;
+/// Exit code for a command line that could not be understood
+pub const EXIT_USAGE: i32 = 2;
+/// Exit code for a program that was still running when it reached `--max-cycles`
+pub const EXIT_MAX_CYCLES: i32 = 3;
+/// Exit code for `check` finding code that assembles but is probably not what was meant
+pub const EXIT_WARNINGS: i32 = 4;
+/// Exit code for a program that was stopped by a fault, such as jumping outside of memory
+pub const EXIT_FAULT: i32 = 5;
+
+pub const USAGE: &str = "\
+usage: cr_cpu_assembler <command> [options] <inputs...>
+
+commands:
+  build <inputs...>   assemble source files, and link them with object files and archives
+  run <input>         run a binary, or assemble and run a source file
+  disasm <input>      print a binary, or an assembled source file, as instructions
+  check <input>       assemble a source file without writing anything, reporting any errors and warnings
+  help                print this message
+
+options:
+  -o <path>                   output path, defaults to the first input with the extension of the format
+  --format bin|obj|lib        what build writes, a binary, an object file or an archive, defaults to bin
+  --listing <path>            write the compile listing to a file
+  --sym <path>                write the address of every label and variable to a file
+  --map <path>                write the linker map to a file
+  -D NAME[=value]             define a constant for conditional assembly, the value defaults to 1
+  -O                          run the optimisation pass
+  --profile debug|release     build profile, defaults to debug
+  --max-cycles <count>        stop run after this many instructions
+  --trace                     print every instruction run executes, with the registers it uses
+  -q                          only print errors
+  -v                          print the compile listing, linker map and timing
+
+exit codes: 0 success, 1 assembly or file error, 2 bad command line, 3 program did not finish within --max-cycles,
+4 check found warnings, 5 program stopped by a fault";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Build,
+    Run,
+    Disasm,
+    Check,
+    Help,
+}
+
+/// What the build command writes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A binary that can be run
+    #[default]
+    Binary,
+    /// A relocatable object file
+    Object,
+    /// An archive of object files
+    Archive,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bin" => Some(OutputFormat::Binary),
+            "obj" => Some(OutputFormat::Object),
+            "lib" => Some(OutputFormat::Archive),
+            _ => None,
+        }
+    }
+
+    /// File extension used for the output when `-o` is not given
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Binary => "bin",
+            OutputFormat::Object => "o",
+            OutputFormat::Archive => "lib",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+}
+
+/// Everything given on the command line
+#[derive(Debug)]
+pub struct CommandLine {
+    pub command: Command,
+    pub inputs: Vec<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub format: OutputFormat,
+    pub listing: Option<PathBuf>,
+    pub sym: Option<PathBuf>,
+    pub map: Option<PathBuf>,
+    pub verbosity: Verbosity,
+    pub max_cycles: Option<usize>,
+    pub trace: bool,
+    pub options: AssemblerOptions,
+}
+
+impl CommandLine {
+    /// Parse the command line arguments, not including the program name
+    /// Returns a message describing the problem if the arguments are not valid
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut args = args.iter();
+        let command = match args.next().map(|arg| arg.as_str()) {
+            Some("build") => Command::Build,
+            Some("run") => Command::Run,
+            Some("disasm") => Command::Disasm,
+            Some("check") => Command::Check,
+            Some("help" | "-h" | "--help") | None => Command::Help,
+            Some(other) => return Err(format!("unknown command '{other}'")),
+        };
+
+        let mut command_line = CommandLine {
+            command,
+            inputs: vec![],
+            output: None,
+            format: OutputFormat::default(),
+            listing: None,
+            sym: None,
+            map: None,
+            verbosity: Verbosity::default(),
+            max_cycles: None,
+            trace: false,
+            options: AssemblerOptions::default(),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().cloned().ok_or_else(|| format!("{arg} expects a value"));
+            match arg.as_str() {
+                "-o" => command_line.output = Some(PathBuf::from(value()?)),
+                "--format" => {
+                    let name = value()?;
+                    command_line.format = OutputFormat::from_name(&name)
+                        .ok_or_else(|| format!("unknown format '{name}', expected bin, obj or lib"))?;
+                }
+                "--listing" => command_line.listing = Some(PathBuf::from(value()?)),
+                "--sym" => command_line.sym = Some(PathBuf::from(value()?)),
+                "--map" => command_line.map = Some(PathBuf::from(value()?)),
+                "-D" => {
+                    let (name, value) = parse_definition(&value()?)?;
+                    command_line.options.definitions.insert(name, value);
+                }
+                "-O" => command_line.options.optimise = true,
+                "--profile" => {
+                    let name = value()?;
+                    command_line.options.profile = Profile::from_name(&name)
+                        .ok_or_else(|| format!("unknown profile '{name}', expected debug or release"))?;
+                }
+                "--max-cycles" => {
+                    let count = value()?;
+                    command_line.max_cycles =
+                        Some(count.parse().map_err(|_| format!("--max-cycles expects a number, found '{count}'"))?);
+                }
+                "--trace" => command_line.trace = true,
+                "-q" => command_line.verbosity = Verbosity::Quiet,
+                "-v" => command_line.verbosity = Verbosity::Verbose,
+                _ if arg.starts_with("-D") && arg.len() > 2 => {
+                    let (name, value) = parse_definition(&arg[2..])?;
+                    command_line.options.definitions.insert(name, value);
+                }
+                _ if arg.starts_with('-') => return Err(format!("unknown option '{arg}'")),
+                _ => command_line.inputs.push(PathBuf::from(arg)),
+            }
+        }
+
+        match command_line.command {
+            Command::Help => {}
+            Command::Build if command_line.inputs.is_empty() => return Err("build expects at least one input".to_string()),
+            Command::Build => {}
+            _ if command_line.inputs.len() != 1 => {
+                return Err(format!("{:?} expects one input", command_line.command).to_lowercase());
+            }
+            _ => {}
+        }
+        Ok(command_line)
+    }
+
+    /// Where the output of the command goes, `-o` or the first input with the extension of the output format
+    pub fn output_path(&self) -> PathBuf {
+        self.output
+            .clone()
+            .unwrap_or_else(|| self.inputs[0].with_extension(self.format.extension()))
+    }
+}
+
+/// Parse a `-D NAME=value` definition, the value is 1 if it is left out
+fn parse_definition(definition: &str) -> Result<(String, u32), String> {
+    let (name, value) = definition.split_once('=').unwrap_or((definition, "1"));
+    if !name.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        return Err(format!("-D expects NAME=value, found '{definition}'"));
+    }
+    let value = parse_word(value).map_err(|msg| format!("-D {name}: {msg}"))?;
+    Ok((name.to_string(), value))
+}
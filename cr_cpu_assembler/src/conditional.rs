@@ -0,0 +1,110 @@
+use crate::error::AssemblerError;
+use crate::expression::evaluate;
+use crate::source::SourceLocation;
+use std::collections::HashMap;
+
+/// An `.if`, `.ifdef` or `.ifndef` block that has been started but not ended with `.endif`
+struct Block {
+    location: SourceLocation,
+    /// True if the lines of the current branch of this block are assembled
+    active: bool,
+    /// True if the lines around this block are assembled
+    outer_active: bool,
+    /// True once `.else` has been found in this block
+    in_else: bool,
+}
+
+/// Keeps track of conditional assembly blocks while reading the program in order,
+/// deciding which lines are assembled and which are skipped
+#[derive(Default)]
+pub struct Conditionals {
+    blocks: Vec<Block>,
+}
+
+impl Conditionals {
+    /// Returns true if lines are currently being assembled, which is when every block around them has its condition met
+    pub fn is_active(&self) -> bool {
+        self.blocks.last().is_none_or(|block| block.active)
+    }
+
+    /// Handle a line if it is a conditional assembly directive, `.if expression`, `.ifdef NAME`, `.ifndef NAME`, `.else` or `.endif`.
+    /// Returns false if the line is not a conditional assembly directive.
+    /// `constants` are the names `.ifdef` counts as defined, and the values `.if` expressions can use
+    pub fn handle(
+        &mut self,
+        line: &[String],
+        location: &SourceLocation,
+        constants: &HashMap<String, u32>,
+    ) -> Result<bool, AssemblerError> {
+        let name = line[0].to_lowercase();
+        match (name.as_str(), &line[1..]) {
+            (".if", [_, ..]) => {
+                // expressions are only evaluated when they matter, so skipped blocks can use constants that are not defined
+                let condition = self.is_active()
+                    && evaluate(&line[1..].join(" "), constants).map_err(|msg| AssemblerError::new(location, msg))? != 0;
+                self.start_block(location, condition);
+            }
+            (".ifdef" | ".ifndef", [symbol]) => {
+                self.start_block(location, constants.contains_key(symbol) == (name == ".ifdef"));
+            }
+            (".if", []) => {
+                return Err(AssemblerError::new(location, ".if expects an expression"));
+            }
+            (".ifdef" | ".ifndef", _) => {
+                return Err(AssemblerError::new(location, format!("{name} expects a name")));
+            }
+            (".else", []) => {
+                let block = self
+                    .blocks
+                    .last_mut()
+                    .ok_or_else(|| AssemblerError::new(location, ".else without .if"))?;
+                if block.in_else {
+                    return Err(AssemblerError::new(location, "more than one .else in the same block"));
+                }
+                block.in_else = true;
+                block.active = block.outer_active && !block.active;
+            }
+            (".endif", []) => {
+                self.blocks
+                    .pop()
+                    .ok_or_else(|| AssemblerError::new(location, ".endif without .if"))?;
+            }
+            (".else" | ".endif", _) => {
+                return Err(AssemblerError::new(location, format!("{name} does not take any arguments")));
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn start_block(&mut self, location: &SourceLocation, condition: bool) {
+        let outer_active = self.is_active();
+        self.blocks.push(Block {
+            location: location.clone(),
+            active: outer_active && condition,
+            outer_active,
+            in_else: false,
+        });
+    }
+
+    /// Check that every block was ended with `.endif`, to be called once the whole program has been read
+    pub fn finish(&self) -> Result<(), AssemblerError> {
+        match self.blocks.last() {
+            Some(block) => Err(AssemblerError::new(&block.location, "this block is never ended with .endif")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parse a `.equ NAME expression` line into the name and value of the constant it defines
+pub fn parse_constant(line: &[String], constants: &HashMap<String, u32>) -> Result<(String, u32), String> {
+    match line {
+        [_, name, expression @ ..] if !expression.is_empty() => {
+            if !name.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+                return Err(format!("invalid constant name {name}"));
+            }
+            Ok((name.to_string(), evaluate(&expression.join(" "), constants)?))
+        }
+        _ => Err(".equ expects a name and a value, e.g. .equ MEMORY 128".to_string()),
+    }
+}
@@ -0,0 +1,154 @@
+use crate::layout::Section;
+use crate::program_instruction::ProgramInstruction;
+use crate::program_instruction::ProgramInstruction::{Addresses, Align, Data, Global, Org, SectionStart};
+use crate::source::unquote;
+use cr_cpu_common::constants::DRAM_SIZE;
+
+/// Returns true if the given item is an assembler directive, requirements being that it starts with '.'
+/// e.g. `.word`
+pub fn is_directive(item: &str) -> bool {
+    item.starts_with('.') && item.len() > 1
+}
+
+/// Parse a directive line into the program instruction it represents
+/// Returns an error message describing what is wrong with the line if it could not be parsed
+pub fn parse_directive(line: &[String]) -> Result<ProgramInstruction, String> {
+    let name = line[0].to_lowercase();
+    let args = &line[1..];
+    match name.as_str() {
+        ".word" => {
+            if args.is_empty() {
+                return Err(".word expects at least one value".to_string());
+            }
+            let words = args
+                .iter()
+                .map(|arg| parse_word(arg))
+                .collect::<Result<Vec<u32>, String>>()?;
+            Ok(Data(words))
+        }
+        ".byte" => {
+            if args.is_empty() {
+                return Err(".byte expects at least one value".to_string());
+            }
+            let bytes = args
+                .iter()
+                .map(|arg| {
+                    let value = parse_word(arg)?;
+                    u8::try_from(value).map_err(|_| format!("{arg} does not fit in a byte"))
+                })
+                .collect::<Result<Vec<u8>, String>>()?;
+            Ok(Data(pack_bytes(&bytes)))
+        }
+        ".string" | ".asciz" => {
+            // .string "text" stores one character per word, .string packed "text" stores four characters per word
+            let (packed, text) = match args {
+                [text] => (false, text),
+                [mode, text] if mode.eq_ignore_ascii_case("packed") => (true, text),
+                _ => {
+                    return Err(format!("{name} expects a quoted string, e.g. {name} \"text\" or {name} packed \"text\""));
+                }
+            };
+            let text = unquote(text).ok_or_else(|| format!("{name} expects a quoted string, found {text}"))?;
+            let words = if packed {
+                let mut bytes = text.into_bytes();
+                bytes.push(0);
+                pack_bytes(&bytes)
+            } else {
+                text.chars().map(|c| c as u32).chain([0]).collect()
+            };
+            Ok(Data(words))
+        }
+        ".addr" => {
+            if args.is_empty() {
+                return Err(".addr expects at least one label or variable".to_string());
+            }
+            Ok(Addresses(args.to_vec()))
+        }
+        ".zero" => match args {
+            [count] => {
+                // checked before the words are made, as a count larger than dram could never be placed anyway
+                let count = parse_word(count)?;
+                if count > DRAM_SIZE {
+                    return Err(format!(".zero of {count} words does not fit in memory of {DRAM_SIZE} words"));
+                }
+                Ok(Data(vec![0; count as usize]))
+            }
+            _ => Err(".zero expects a word count".to_string()),
+        },
+        ".align" => match args {
+            [alignment] => {
+                let alignment = parse_word(alignment)?;
+                if alignment == 0 {
+                    return Err(".align can not align to 0 words".to_string());
+                }
+                if alignment > DRAM_SIZE {
+                    return Err(format!(".align to {alignment} words does not fit in memory of {DRAM_SIZE} words"));
+                }
+                Ok(Align(alignment))
+            }
+            _ => Err(".align expects a word alignment".to_string()),
+        },
+        ".org" => match args {
+            [address] => Ok(Org(parse_address(address, ".org")?)),
+            _ => Err(".org expects an address".to_string()),
+        },
+        ".section" => {
+            // .section name, or .section name base_address
+            let (section, base) = match args {
+                [section] => (section, None),
+                [section, base] => (section, Some(parse_address(base, ".section")?)),
+                _ => return Err(".section expects a section name and an optional base address".to_string()),
+            };
+            let section = Section::from_name(section)
+                .ok_or_else(|| format!("unknown section {section}, expected text, data or bss"))?;
+            Ok(SectionStart(section, base))
+        }
+        ".text" | ".data" | ".bss" if args.is_empty() => {
+            Ok(SectionStart(Section::from_name(&name[1..]).unwrap(), None))
+        }
+        ".global" | ".globl" => match args {
+            [symbol] => Ok(Global(symbol.to_string())),
+            _ => Err(format!("{name} expects a label name")),
+        },
+        _ => Err(format!("unknown directive: {name}")),
+    }
+}
+
+/// Parse a number literal as a u32, numbers can be decimal, hexadecimal with 0x, or binary with 0b.
+/// Negative decimal numbers are stored as their twos complement
+pub fn parse_word(text: &str) -> Result<u32, String> {
+    let lower = text.to_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        u32::from_str_radix(bin, 2).ok()
+    } else if lower.starts_with('-') {
+        lower.parse::<i32>().ok().map(|num| num as u32)
+    } else {
+        lower.parse::<u32>().ok()
+    };
+    parsed.ok_or_else(|| format!("invalid number: {text}"))
+}
+
+/// Parse an address given to `directive`, which can be at most the end of dram.
+/// Checked here so the location counters of the layout can never get near overflowing
+fn parse_address(text: &str, directive: &str) -> Result<u32, String> {
+    let address = parse_word(text)?;
+    if address > DRAM_SIZE {
+        return Err(format!("{directive} address {address} is outside of memory of {DRAM_SIZE} words"));
+    }
+    Ok(address)
+}
+
+/// Pack bytes into words, four bytes per word in little endian order, the last word is padded with zeros
+fn pack_bytes(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks(4)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0, |word, (index, byte)| word | (*byte as u32) << (index * 8))
+        })
+        .collect()
+}
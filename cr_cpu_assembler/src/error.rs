@@ -0,0 +1,38 @@
+use crate::source::SourceLocation;
+use std::fmt::{Display, Formatter};
+
+/// An error found while assembling a program, pointing at the source line that caused it when known
+#[derive(Debug, Clone)]
+pub struct AssemblerError {
+    pub location: Option<SourceLocation>,
+    pub message: String,
+}
+
+impl AssemblerError {
+    /// Create an error that was caused by a specific line of source code
+    pub fn new(location: &SourceLocation, message: impl Into<String>) -> Self {
+        Self {
+            location: Some(location.clone()),
+            message: message.into(),
+        }
+    }
+
+    /// Create an error that is not tied to any line of source code, e.g. a missing input file
+    pub fn without_location(message: impl Into<String>) -> Self {
+        Self {
+            location: None,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for AssemblerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "error: {}: {}", location, self.message),
+            None => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for AssemblerError {}
@@ -0,0 +1,165 @@
+use crate::directive::parse_word;
+use std::collections::HashMap;
+
+/// Binary operators from lowest to highest precedence, operators on the same level are evaluated left to right
+const BINARY_OPERATORS: [&[&str]; 9] = [
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<=", ">=", "<", ">"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// Evaluate an assembly time expression such as `MEMORY >= 128 && defined(TRACE)`.
+/// Numbers are written the same as in data directives, names are looked up in `constants`,
+/// and `defined(NAME)` is 1 if the name is a constant and 0 if not.
+/// Comparisons and logical operators give 1 for true and 0 for false, arithmetic wraps around.
+/// `&&` and `||` only evaluate their right side if it is needed, so `defined(NAME) && NAME > 1` works when NAME is not defined
+pub fn evaluate(text: &str, constants: &HashMap<String, u32>) -> Result<u32, String> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+        constants,
+        skipping: false,
+    };
+    let value = parser.binary(0)?;
+    match parser.tokens.get(parser.position) {
+        Some(token) => Err(format!("unexpected {token} in expression")),
+        None => Ok(value),
+    }
+}
+
+/// Split an expression into numbers, names, operators and brackets
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c.is_alphanumeric() || c == '_' {
+            let mut token = c.to_string();
+            while let Some(next) = chars.next_if(|next| next.is_alphanumeric() || *next == '_') {
+                token.push(next);
+            }
+            tokens.push(token);
+        } else if "()+-*/%^!<>=&|".contains(c) {
+            let pair = chars.peek().map(|next| format!("{c}{next}"));
+            match pair {
+                Some(pair) if ["||", "&&", "==", "!=", "<=", ">="].contains(&pair.as_str()) => {
+                    chars.next();
+                    tokens.push(pair);
+                }
+                _ => tokens.push(c.to_string()),
+            }
+        } else {
+            return Err(format!("unexpected character '{c}' in expression"));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    position: usize,
+    constants: &'a HashMap<String, u32>,
+    /// True while parsing the side of `&&` or `||` that is not needed, where unknown constants and division by zero are not errors
+    skipping: bool,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<&str> {
+        let token = self.tokens.get(self.position)?;
+        self.position += 1;
+        Some(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("expected {expected} in expression, found {token}")),
+            None => Err(format!("expected {expected} at the end of the expression")),
+        }
+    }
+
+    /// Parse binary operators of the given precedence level and above
+    fn binary(&mut self, level: usize) -> Result<u32, String> {
+        if level == BINARY_OPERATORS.len() {
+            return self.unary();
+        }
+        let mut value = self.binary(level + 1)?;
+        while let Some(operator) = self
+            .tokens
+            .get(self.position)
+            .filter(|token| BINARY_OPERATORS[level].contains(&token.as_str()))
+            .cloned()
+        {
+            self.position += 1;
+            let skip_right = (operator == "&&" && value == 0) || (operator == "||" && value != 0);
+            let was_skipping = self.skipping;
+            self.skipping |= skip_right;
+            let right = self.binary(level + 1);
+            self.skipping = was_skipping;
+            let right = right?;
+            value = match operator.as_str() {
+                "||" => (value != 0 || right != 0) as u32,
+                "&&" => (value != 0 && right != 0) as u32,
+                "|" => value | right,
+                "^" => value ^ right,
+                "&" => value & right,
+                "==" => (value == right) as u32,
+                "!=" => (value != right) as u32,
+                "<=" => (value <= right) as u32,
+                ">=" => (value >= right) as u32,
+                "<" => (value < right) as u32,
+                ">" => (value > right) as u32,
+                "+" => value.wrapping_add(right),
+                "-" => value.wrapping_sub(right),
+                "*" => value.wrapping_mul(right),
+                "/" | "%" if right == 0 && self.skipping => 0,
+                "/" | "%" if right == 0 => return Err("division by zero in expression".to_string()),
+                "/" => value / right,
+                "%" => value % right,
+                _ => unreachable!(),
+            };
+        }
+        Ok(value)
+    }
+
+    /// Parse `!value`, `-value`, or a single value
+    fn unary(&mut self) -> Result<u32, String> {
+        match self.next() {
+            Some("!") => Ok((self.unary()? == 0) as u32),
+            Some("-") => Ok(self.unary()?.wrapping_neg()),
+            Some("(") => {
+                let value = self.binary(0)?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            Some("defined") => {
+                self.expect("(")?;
+                let name = self.next().ok_or("expected a name after defined(")?.to_string();
+                self.expect(")")?;
+                Ok(self.constants.contains_key(&name) as u32)
+            }
+            Some(token) if token.starts_with(|c: char| c.is_ascii_digit()) => parse_word(token),
+            Some(token) if !token.starts_with(|c: char| c.is_alphabetic() || c == '_') => {
+                Err(format!("unexpected {token} in expression"))
+            }
+            Some(token) => {
+                let name = token.to_string();
+                match self.constants.get(&name) {
+                    Some(value) => Ok(*value),
+                    None if self.skipping => Ok(0),
+                    None => Err(format!("unknown constant {name} in expression")),
+                }
+            }
+            None => Err("expected a value at the end of the expression".to_string()),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::error::AssemblerError;
+use crate::source::SourceLocation;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Prefix given to the generated names of anonymous labels
+const ANONYMOUS_PREFIX: &str = "@anonymous";
+/// Prefix given to the names of labels the assembler creates for itself, such as the target of `nop`
+const INTERNAL_PREFIX: &str = "@internal";
+
+/// Returns true if the given item is a label, requirements being that it starts and ends with ':'
+/// e.g. `:this_is_a_label:`, `:.local_label:` or the anonymous label `::`
+pub fn is_label(item: &str) -> bool {
+    item.len() >= 2 && item.starts_with(':') && item.ends_with(':')
+}
+
+/// Returns true if the label was created by the assembler with `LabelScope::internal`
+pub fn is_internal_label(name: &str) -> bool {
+    name.starts_with(INTERNAL_PREFIX)
+}
+
+/// Returns true if a label reference is to an anonymous label, e.g. `-`, `--`, `+` or `++`
+fn is_anonymous_reference(name: &str) -> bool {
+    !name.is_empty() && (name.chars().all(|c| c == '-') || name.chars().all(|c| c == '+'))
+}
+
+/// Keeps track of which global label local labels belong to, and how many anonymous labels have been defined,
+/// so that label definitions and references can be turned into unique label names while reading the program in order
+#[derive(Default)]
+pub struct LabelScope {
+    /// The most recent global label in each file
+    globals: HashMap<PathBuf, String>,
+    /// Number of anonymous labels defined so far
+    anonymous_count: u32,
+    /// Number of internal labels created so far
+    internal_count: u32,
+    /// Anonymous labels referenced with `+` that must be defined later in the program
+    forward_references: Vec<(u32, SourceLocation)>,
+}
+
+impl LabelScope {
+    /// Get the unique name of a label definition such as `:main:`, `:.loop:` or `::`
+    /// Local labels starting with '.' are named after the global label before them, e.g. `main.loop`
+    pub fn define(&mut self, label: &str, location: &SourceLocation) -> String {
+        let name = label.trim_matches(':');
+        if name.is_empty() {
+            self.anonymous_count += 1;
+            format!("{ANONYMOUS_PREFIX}{}", self.anonymous_count - 1)
+        } else if name.starts_with('.') {
+            format!("{}{name}", self.global(location))
+        } else {
+            self.globals.insert(location.file.clone(), name.to_string());
+            name.to_string()
+        }
+    }
+
+    /// Get the unique name of the label a reference such as `main`, `.loop`, `-` or `++` points to
+    pub fn reference(&mut self, name: &str, location: &SourceLocation) -> Result<String, AssemblerError> {
+        if is_anonymous_reference(name) {
+            let distance = name.len() as u32;
+            let index = if name.starts_with('-') {
+                self.anonymous_count.checked_sub(distance).ok_or_else(|| {
+                    AssemblerError::new(
+                        location,
+                        format!("{name} refers back {distance} anonymous labels, but only {} are defined before this line", self.anonymous_count),
+                    )
+                })?
+            } else {
+                let index = self.anonymous_count + distance - 1;
+                self.forward_references.push((index, location.clone()));
+                index
+            };
+            Ok(format!("{ANONYMOUS_PREFIX}{index}"))
+        } else if name.starts_with('.') {
+            Ok(format!("{}{name}", self.global(location)))
+        } else {
+            Ok(name.to_string())
+        }
+    }
+
+    /// Create a unique label name for the assembler's own use, which does not change the scope of local labels
+    pub fn internal(&mut self) -> String {
+        self.internal_count += 1;
+        format!("{INTERNAL_PREFIX}{}", self.internal_count - 1)
+    }
+
+    /// Check that every anonymous label referenced with `+` was defined, to be called once the whole program has been read
+    pub fn finish(&self) -> Result<(), AssemblerError> {
+        match self
+            .forward_references
+            .iter()
+            .find(|(index, _)| *index >= self.anonymous_count)
+        {
+            Some((_, location)) => Err(AssemblerError::new(
+                location,
+                "there are not enough anonymous labels after this line",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn global(&self, location: &SourceLocation) -> &str {
+        self.globals.get(&location.file).map(|name| name.as_str()).unwrap_or("")
+    }
+}
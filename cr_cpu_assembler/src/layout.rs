@@ -0,0 +1,185 @@
+use crate::error::AssemblerError;
+use crate::program_instruction::ProgramInstruction;
+use crate::program_instruction::ProgramInstruction::*;
+use crate::source::SourceLocation;
+use cr_cpu_common::constants::DRAM_SIZE;
+use std::collections::HashMap;
+
+/// A section of the program, each section is placed in its own area of dram
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Section {
+    /// Instructions
+    Text,
+    /// Initialized data
+    Data,
+    /// Zero initialized data
+    Bss,
+}
+
+impl Section {
+    /// Every section, in the order they are placed in dram when they do not have a base address
+    pub const ALL: [Section; 3] = [Section::Text, Section::Data, Section::Bss];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "text" => Some(Section::Text),
+            "data" => Some(Section::Data),
+            "bss" => Some(Section::Bss),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Section::Text => "text",
+            Section::Data => "data",
+            Section::Bss => "bss",
+        }
+    }
+}
+
+/// A range of dram addresses taken up by part of the program, `start..end`
+#[derive(Clone, Debug)]
+pub struct Region {
+    pub name: String,
+    pub start: u32,
+    pub end: u32,
+    /// The line that started this region, if it came from source code
+    pub location: Option<SourceLocation>,
+}
+
+/// Where every program instruction is placed in dram
+pub struct Layout {
+    /// Address of each program instruction, in the same order as the instructions
+    pub addresses: Vec<u32>,
+    /// Section each program instruction was placed in, in the same order as the instructions
+    pub sections: Vec<Section>,
+    /// Address each section starts at
+    pub section_starts: HashMap<Section, u32>,
+    pub regions: Vec<Region>,
+}
+
+/// Give every program instruction an address, using a location counter for each section.
+/// Sections without a base address set by `.section name base` are placed directly after the previous section,
+/// the first section starting at `start_address`. `.org address` moves the location counter of the current section.
+/// `reserved` regions are parts of dram that are already taken, and are included in the overlap check.
+pub fn layout_program(
+    instructions: &[(ProgramInstruction, SourceLocation)],
+    start_address: u32,
+    reserved: Vec<Region>,
+) -> Result<Layout, AssemblerError> {
+    // find which section each instruction belongs to, and the base addresses given to sections
+    let mut sections = Vec::with_capacity(instructions.len());
+    let mut bases: HashMap<Section, u32> = HashMap::new();
+    {
+        let mut current = Section::Text;
+        for (inst, location) in instructions {
+            if let SectionStart(section, base) = inst {
+                current = *section;
+                if let Some(base) = base {
+                    if bases.insert(current, *base).is_some_and(|old| old != *base) {
+                        return Err(AssemblerError::new(
+                            location,
+                            format!("section {} is given more than one base address", current.name()),
+                        ));
+                    }
+                }
+            }
+            if current == Section::Bss && !is_bss_friendly(inst) {
+                return Err(AssemblerError::new(
+                    location,
+                    "the bss section can only contain labels, .zero and .align",
+                ));
+            }
+            sections.push(current);
+        }
+    }
+
+    let mut addresses = vec![0; instructions.len()];
+    let mut section_starts = HashMap::new();
+    let mut regions = reserved;
+    let mut next_base = start_address;
+    for section in Section::ALL {
+        let base = bases.get(&section).copied().unwrap_or(next_base);
+        section_starts.insert(section, base);
+        let mut location_counter = base;
+        let mut region: Option<Region> = None;
+
+        for (index, (inst, location)) in instructions.iter().enumerate() {
+            if sections[index] != section {
+                continue;
+            }
+            if let Org(address) = inst {
+                // moving the location counter starts a new region of the program
+                regions.extend(region.take());
+                location_counter = *address;
+            }
+            addresses[index] = location_counter;
+            let size = inst.size_at(location_counter);
+            if size > 0 {
+                let region = region.get_or_insert_with(|| Region {
+                    name: section.name().to_string(),
+                    start: location_counter,
+                    end: location_counter,
+                    location: Some(location.clone()),
+                });
+                region.end = location_counter + size;
+            }
+            location_counter += size;
+        }
+        regions.extend(region);
+        next_base = location_counter;
+    }
+
+    check_regions(&regions)?;
+
+    Ok(Layout {
+        addresses,
+        sections,
+        section_starts,
+        regions,
+    })
+}
+
+/// Items that are allowed in the bss section, which must not contain anything but zeros
+fn is_bss_friendly(inst: &ProgramInstruction) -> bool {
+    match inst {
+        Data(words) => words.iter().all(|word| *word == 0),
+        Label(_) | Align(_) | Org(_) | SectionStart(_, _) | Global(_) => true,
+        Asm(_) | PreAsm(_, _) | Addresses(_) | Variable(_, _) => false,
+    }
+}
+
+/// Check that every region fits in dram and that no two regions share an address
+fn check_regions(regions: &[Region]) -> Result<(), AssemblerError> {
+    let describe = |region: &Region| match &region.location {
+        Some(location) => format!("{} at {}..{} ({location})", region.name, region.start, region.end),
+        None => format!("{} at {}..{}", region.name, region.start, region.end),
+    };
+    let error = |region: &Region, message: String| match &region.location {
+        Some(location) => AssemblerError::new(location, message),
+        None => AssemblerError::without_location(message),
+    };
+
+    let mut sorted: Vec<&Region> = regions.iter().filter(|region| region.start < region.end).collect();
+    sorted.sort_by_key(|region| region.start);
+
+    for region in &sorted {
+        if region.end > DRAM_SIZE {
+            return Err(error(
+                region,
+                format!("{} does not fit in {DRAM_SIZE} words of dram", describe(region)),
+            ));
+        }
+    }
+    for pair in sorted.windows(2) {
+        if pair[1].start < pair[0].end {
+            return Err(error(
+                pair[1],
+                format!("{} overlaps {}", describe(pair[1]), describe(pair[0])),
+            ));
+        }
+    }
+
+    Ok(())
+}
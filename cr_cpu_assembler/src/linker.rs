@@ -0,0 +1,146 @@
+use crate::error::AssemblerError;
+use crate::layout::Section;
+use crate::object_file::{ObjectFile, SectionAddresses};
+use cr_cpu_common::constants::DRAM_SIZE;
+use cr_cpu_common::prelude::Cpu;
+use std::collections::HashMap;
+
+/// Zero words left after the text section of every object, two unknown instructions in a row end the program,
+/// so code that runs off the end of its text stops there instead of running into the next object
+const TEXT_END_WORDS: u32 = 2;
+
+/// A program built by linking object files together
+pub struct LinkedProgram {
+    pub cpu: Cpu,
+    /// Text description of where every section and exported symbol was placed
+    pub map: String,
+    /// Every exported symbol and its address, sorted by address
+    pub symbols: Vec<(String, u32)>,
+}
+
+/// Combine object files into a single program.
+/// Sections are placed one after another starting at address 0, the text sections of every object first, then data, then bss,
+/// so the first object's text section is where the program starts running.
+/// Every text section is followed by `TEXT_END_WORDS` zero words, which also give a label at the end of the text somewhere to point.
+/// Every reference to a symbol is filled in with the symbol's final address,
+/// symbols defined in the same object are used first, then symbols exported by any object with `.global`.
+pub fn link(objects: &[ObjectFile]) -> Result<LinkedProgram, AssemblerError> {
+    // place every section of every object
+    let mut addresses: SectionAddresses = vec![HashMap::new(); objects.len()];
+    let mut next_address: u32 = 0;
+    for section in Section::ALL {
+        for (index, object) in objects.iter().enumerate() {
+            addresses[index].insert(section, next_address);
+            let mut size = object.section_size(section);
+            if section == Section::Text && size > 0 {
+                size += TEXT_END_WORDS;
+            }
+            // sizes come from object files, so they are checked before anything is placed past the end of dram
+            next_address = next_address
+                .checked_add(size)
+                .filter(|end| *end <= DRAM_SIZE)
+                .ok_or_else(|| {
+                    AssemblerError::without_location(format!(
+                        "linked program does not fit in {DRAM_SIZE} words of dram, the {} section of {} ends past it",
+                        section.name(),
+                        object.name
+                    ))
+                })?;
+        }
+    }
+
+    // build the table of exported symbols, making sure each one is only exported once
+    let mut globals: HashMap<&str, (usize, u32)> = HashMap::new();
+    let mut problems = vec![];
+    for (index, object) in objects.iter().enumerate() {
+        for symbol in object.exports() {
+            let address = addresses[index][&symbol.section] + symbol.offset;
+            if let Some((other, _)) = globals.insert(&symbol.name, (index, address)) {
+                problems.push(format!(
+                    "duplicate symbol {}: exported by {} and {}",
+                    symbol.name, objects[other].name, object.name
+                ));
+            }
+        }
+    }
+
+    // fill in every relocation
+    let mut cpu = Cpu::new();
+    for (index, object) in objects.iter().enumerate() {
+        let mut text = object.text.clone();
+        let mut data = object.data.clone();
+        for relocation in &object.relocations {
+            let symbol_address = match object.find_symbol(&relocation.symbol) {
+                Some(symbol) => addresses[index][&symbol.section] + symbol.offset,
+                None => match globals.get(relocation.symbol.as_str()) {
+                    Some((_, address)) => *address,
+                    None => {
+                        problems.push(format!("undefined symbol {}: used by {}", relocation.symbol, object.name));
+                        continue;
+                    }
+                },
+            };
+            if relocation.width < 32 && symbol_address >> relocation.width != 0 {
+                problems.push(format!(
+                    "address of {} ({symbol_address}) does not fit in {} bits: used by {}",
+                    relocation.symbol, relocation.width, object.name
+                ));
+                continue;
+            }
+
+            let words = match relocation.section {
+                Section::Text => &mut text,
+                Section::Data => &mut data,
+                Section::Bss => {
+                    problems.push(format!("relocation in the bss section of {}", object.name));
+                    continue;
+                }
+            };
+            let mask = (u32::MAX >> (32 - relocation.width)) << relocation.shift;
+            match words.get_mut(relocation.offset as usize) {
+                Some(word) => *word = (*word & !mask) | (symbol_address << relocation.shift),
+                None => problems.push(format!("relocation outside of its section in {}", object.name)),
+            }
+        }
+        cpu.write_dram(addresses[index][&Section::Text], &text);
+        cpu.write_dram(addresses[index][&Section::Data], &data);
+    }
+
+    if !problems.is_empty() {
+        return Err(AssemblerError::without_location(problems.join("\n")));
+    }
+
+    let mut symbols: Vec<(String, u32)> = globals
+        .iter()
+        .map(|(name, (_, address))| (name.to_string(), *address))
+        .collect();
+    symbols.sort_by_key(|(name, address)| (*address, name.clone()));
+
+    Ok(LinkedProgram {
+        cpu,
+        map: linker_map(objects, &addresses, &globals),
+        symbols,
+    })
+}
+
+/// Describe where every section and exported symbol was placed
+fn linker_map(objects: &[ObjectFile], addresses: &SectionAddresses, globals: &HashMap<&str, (usize, u32)>) -> String {
+    let mut map = "Sections:\n".to_string();
+    for section in Section::ALL {
+        for (index, object) in objects.iter().enumerate() {
+            let size = object.section_size(section);
+            if size > 0 {
+                let start = addresses[index][&section];
+                map += &format!("{:<6} [{}..{}] {}\n", section.name(), start, start + size, object.name);
+            }
+        }
+    }
+
+    map += "\nSymbols:\n";
+    let mut symbols: Vec<(&&str, &(usize, u32))> = globals.iter().collect();
+    symbols.sort_by_key(|(name, (_, address))| (*address, **name));
+    for (name, (index, address)) in symbols {
+        map += &format!("{address:<6} {name} ({})\n", objects[*index].name);
+    }
+    map
+}
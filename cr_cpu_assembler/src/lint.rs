@@ -0,0 +1,253 @@
+use crate::layout::Section;
+use crate::program_instruction::ProgramInstruction;
+use crate::program_instruction::ProgramInstruction::*;
+use crate::source::SourceLocation;
+use cr_cpu_common::instruction::Instruction;
+use cr_cpu_common::instruction::Instruction::{Call, JmpR, Lea, MoveA, Ret, JMP};
+use cr_cpu_common::prelude::Cpu;
+use cr_cpu_common::PCReference;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// A problem found by `check` in a program that can still be assembled
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub location: SourceLocation,
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "warning: {}: {}", self.location, self.message)
+    }
+}
+
+/// A program instruction and where `compile` placed it
+#[derive(Clone, Debug)]
+pub struct PlacedInstruction {
+    pub inst: ProgramInstruction,
+    pub location: SourceLocation,
+    pub address: u32,
+    pub section: Section,
+}
+
+/// How an instruction uses the address in its operand
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Jump,
+    Read,
+    Write,
+}
+
+/// The address an instruction jumps to, reads from or writes to, if it has one
+fn memory_access(inst: &Instruction) -> Option<(Access, u32)> {
+    if let Some(address) = inst.jump_target() {
+        return Some((Access::Jump, address as u32));
+    }
+    match inst {
+        Call(address) => Some((Access::Jump, *address as u32)),
+        Lea(address) => Some((Access::Read, *address as u32)),
+        MoveA(address, _) => Some((Access::Write, *address as u32)),
+        _ => None,
+    }
+}
+
+/// Everything the lints need to know about the program, built once from the placed instructions
+struct Program<'a> {
+    placed: &'a [PlacedInstruction],
+    /// Every instruction with its operand resolved to an address, and the symbol it was resolved from
+    instructions: Vec<(&'a PlacedInstruction, Instruction, Option<&'a str>)>,
+    /// Section of every dram word the program takes up, the prologue being in the text section
+    occupied: HashMap<u32, Section>,
+    /// Address range of every instruction, including the prologue, `start..end`
+    ranges: Vec<(u32, u32, Instruction)>,
+    variables: &'a HashMap<String, PCReference>,
+    /// Address the program starts running from, straight after the prologue
+    entry: u32,
+}
+
+/// Look for code that assembles but is probably not what was meant, used by `check`.
+/// The lints are unreachable code after an unconditional jump or return, jumps into the middle of a multi-word instruction,
+/// labels and variables that are never used, `movea` writing into code, and reads of memory nothing ever writes to.
+/// `prologue` is the stack pointer setup placed at address 0, if the program has one
+pub fn lint(
+    placed: &[PlacedInstruction],
+    labels: &HashMap<String, PCReference>,
+    variables: &HashMap<String, PCReference>,
+    prologue: Option<&Instruction>,
+) -> Vec<Warning> {
+    let prologue_len = prologue.map(|inst| inst.to_instruction_data().len() as u32).unwrap_or(0);
+    let mut instructions = vec![];
+    for item in placed {
+        match &item.inst {
+            Asm(inst) => instructions.push((item, inst.clone(), None)),
+            PreAsm(inst, symbol) => {
+                let mut inst = inst.clone();
+                // compile has already checked that every symbol exists and fits
+                if let Some(address) = labels.get(symbol).or_else(|| variables.get(symbol)) {
+                    inst.set_address_operand(address.0);
+                }
+                instructions.push((item, inst, Some(symbol.as_str())));
+            }
+            _ => {}
+        }
+    }
+
+    let mut occupied: HashMap<u32, Section> = (0..prologue_len).map(|address| (address, Section::Text)).collect();
+    for item in placed {
+        let size = item.inst.size_at(item.address);
+        occupied.extend((item.address..item.address + size).map(|address| (address, item.section)));
+    }
+    let ranges = prologue
+        .map(|inst| (0, prologue_len, inst.clone()))
+        .into_iter()
+        .chain(
+            instructions
+                .iter()
+                .map(|(item, inst, _)| (item.address, item.address + inst.to_instruction_data().len() as u32, inst.clone())),
+        )
+        .collect();
+
+    let program = Program {
+        placed,
+        instructions,
+        occupied,
+        ranges,
+        variables,
+        entry: prologue_len,
+    };
+    let mut warnings = vec![];
+    warnings.extend(unreachable_code(&program));
+    warnings.extend(jumps_into_instructions(&program));
+    warnings.extend(unused_symbols(&program, labels));
+    warnings.extend(writes_into_code(&program));
+    warnings.extend(uninitialised_reads(&program));
+    // reported in the order of the source, the sort is stable so each line keeps the order of the lints
+    warnings.sort_by(|a, b| (&a.location.file, a.location.line).cmp(&(&b.location.file, b.location.line)));
+    warnings
+}
+
+/// Instructions that directly follow an unconditional jump or a return, with no label in between for anything to jump to
+fn unreachable_code(program: &Program) -> Vec<Warning> {
+    let mut warnings = vec![];
+    let mut after_jump = false;
+    for item in program.placed {
+        match &item.inst {
+            Asm(inst) | PreAsm(inst, _) => {
+                if after_jump {
+                    warnings.push(Warning {
+                        location: item.location.clone(),
+                        message: "unreachable code after an unconditional jump or return".to_string(),
+                    });
+                }
+                after_jump = matches!(inst, JMP(_) | JmpR(_) | Ret);
+            }
+            // anything could jump to a label, and .org or a new section moves on to somewhere else in dram
+            Label(_) | Org(_) | SectionStart(_, _) => after_jump = false,
+            // data after a jump is common, e.g. a table that the code reads, and the code after it is still unreachable
+            Data(_) | Addresses(_) | Align(_) | Variable(_, _) | Global(_) => {}
+        }
+    }
+    warnings
+}
+
+/// Jumps to an address that is not the first word of an instruction
+fn jumps_into_instructions(program: &Program) -> Vec<Warning> {
+    let mut warnings = vec![];
+    for (item, inst, _) in &program.instructions {
+        let Some((Access::Jump, target)) = memory_access(inst) else {
+            continue;
+        };
+        if let Some((start, _, target_inst)) = program.ranges.iter().find(|(start, end, _)| *start < target && target < *end) {
+            warnings.push(Warning {
+                location: item.location.clone(),
+                message: format!("jump to address {target} lands in the middle of {target_inst:?} at address {start}"),
+            });
+        }
+    }
+    warnings
+}
+
+/// Labels and variables that nothing refers to, ignoring labels that are exported, made by the assembler, or where the program starts
+fn unused_symbols(program: &Program, labels: &HashMap<String, PCReference>) -> Vec<Warning> {
+    let used: HashSet<&str> = program
+        .placed
+        .iter()
+        .flat_map(|item| match &item.inst {
+            PreAsm(_, symbol) | Global(symbol) => vec![symbol.as_str()],
+            Addresses(names) => names.iter().map(String::as_str).collect(),
+            _ => vec![],
+        })
+        .collect();
+
+    let mut warnings = vec![];
+    for item in program.placed {
+        let (kind, name) = match &item.inst {
+            // the label the program starts running at is used by running the program
+            Label(name) if labels.get(name).is_some_and(|address| address.0 != program.entry) => ("label", name),
+            Variable(name, _) if program.variables.contains_key(name) => ("variable", name),
+            _ => continue,
+        };
+        if !name.starts_with('@') && !used.contains(name.as_str()) {
+            warnings.push(Warning {
+                location: item.location.clone(),
+                message: format!("{kind} {name} is never used"),
+            });
+        }
+    }
+    warnings
+}
+
+/// `movea` instructions that write over the program's own instructions
+fn writes_into_code(program: &Program) -> Vec<Warning> {
+    let mut warnings = vec![];
+    for (item, inst, symbol) in &program.instructions {
+        let Some((Access::Write, target)) = memory_access(inst) else {
+            continue;
+        };
+        let is_code = program.ranges.iter().any(|(start, end, _)| *start <= target && target < *end);
+        if is_code {
+            let name = symbol.map(str::to_string).unwrap_or_else(|| format!("address {target}"));
+            warnings.push(Warning {
+                location: item.location.clone(),
+                message: format!("movea writes into code at {name}"),
+            });
+        }
+    }
+    warnings
+}
+
+/// `lea` instructions that read memory which starts as zero and that no `movea` ever writes to,
+/// either reserved in the bss section or not part of the program at all.
+/// The stack area is left out, since it is written to by push
+fn uninitialised_reads(program: &Program) -> Vec<Warning> {
+    let stack_start = Cpu::default().get_sp();
+    let written: HashSet<u32> = program
+        .instructions
+        .iter()
+        .filter_map(|(_, inst, _)| match memory_access(inst) {
+            Some((Access::Write, address)) => Some(address),
+            _ => None,
+        })
+        .collect();
+    let is_variable = |address: u32| program.variables.values().any(|variable| variable.0 == address);
+
+    let mut warnings = vec![];
+    for (item, inst, symbol) in &program.instructions {
+        let Some((Access::Read, target)) = memory_access(inst) else {
+            continue;
+        };
+        let uninitialised = match program.occupied.get(&target) {
+            Some(section) => *section == Section::Bss,
+            None => target < stack_start,
+        };
+        if uninitialised && !is_variable(target) && !written.contains(&target) {
+            let name = symbol.map(str::to_string).unwrap_or_else(|| format!("address {target}"));
+            warnings.push(Warning {
+                location: item.location.clone(),
+                message: format!("lea reads {name}, which is never written and is always 0"),
+            });
+        }
+    }
+    warnings
+}
@@ -1,51 +1,212 @@
-use crate::program_file::ProgramFile;
-use std::fs::File;
-use std::path::PathBuf;
+use crate::archive::{select_members, Archive, ARCHIVE_HEADER};
+use crate::cli::{Command, CommandLine, OutputFormat, Verbosity, EXIT_ERROR, EXIT_FAULT, EXIT_MAX_CYCLES, EXIT_USAGE, EXIT_WARNINGS, USAGE};
+use crate::error::AssemblerError;
+use crate::linker::link;
+use crate::object_file::ObjectFile;
+use crate::program_file::{write_binary, AssemblerOptions, ProgramFile};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
-use std::{env};
-#[cfg(debug_assertions)]
-use std::fs;
+use std::{env, fs, process};
 
+mod archive;
+mod cli;
+mod conditional;
+mod directive;
+mod error;
+mod expression;
+mod labels;
+mod layout;
+mod linker;
+mod lint;
+mod object_file;
+mod optimise;
+mod profile;
 mod program_file;
 mod program_instruction;
+mod pseudo;
+mod source;
+
+/// Print an assembler error and exit, or unwrap the value if there was no error
+fn exit_on_error<T>(result: Result<T, AssemblerError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(EXIT_ERROR);
+        }
+    }
+}
+
+/// Returns true if the path is assembly source code
+fn is_source(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "cr")
+}
+
+/// Write text to a file, for listings, symbol tables and maps
+fn write_text(path: &Path, text: &str) -> Result<(), AssemblerError> {
+    fs::write(path, text)
+        .map_err(|err| AssemblerError::without_location(format!("unable to write '{}': {err}", path.display())))
+}
+
+/// Read an object file, or assemble a `.cr` source file into an object
+fn read_object(path: &Path, options: &AssemblerOptions) -> Result<ObjectFile, AssemblerError> {
+    if is_source(path) {
+        let pf = ProgramFile::new(path.to_path_buf(), path.to_path_buf(), options.clone())?;
+        pf.assemble_object()
+    } else {
+        ObjectFile::read(path)
+    }
+}
+
+/// Assemble a source file into a program, writing its listing and symbol table if they were asked for
+fn compile(command_line: &CommandLine, input: &Path, output: PathBuf) -> Result<ProgramFile, AssemblerError> {
+    let mut pf = ProgramFile::new(input.to_path_buf(), output, command_line.options.clone())?;
+    pf.compile()?;
+    if command_line.verbosity == Verbosity::Verbose {
+        print!("{}", pf.listing());
+    }
+    if let Some(listing) = &command_line.listing {
+        write_text(listing, pf.listing())?;
+    }
+    if let Some(sym) = &command_line.sym {
+        write_text(sym, &pf.symbol_table())?;
+    }
+    Ok(pf)
+}
+
+/// Load a program to run or disassemble, assembling it first if it is source code
+fn load_program(command_line: &CommandLine) -> Result<ProgramFile, AssemblerError> {
+    let input = &command_line.inputs[0];
+    if is_source(input) {
+        let pf = compile(command_line, input, command_line.output_path())?;
+        if command_line.output.is_some() {
+            pf.output_binary()?;
+        }
+        Ok(pf)
+    } else {
+        ProgramFile::new_from_binary(input.clone())
+            .map_err(|err| AssemblerError::without_location(format!("unable to read '{}': {err}", input.display())))
+    }
+}
+
+/// Build the inputs into the output format, linking when there is more than one input or the inputs are not all source code
+fn build(command_line: &CommandLine) -> Result<(), AssemblerError> {
+    let output = command_line.output_path();
+    let inputs = &command_line.inputs;
+    match command_line.format {
+        OutputFormat::Binary if inputs.len() == 1 && is_source(&inputs[0]) => {
+            compile(command_line, &inputs[0], output.clone())?.output_binary()?;
+        }
+        OutputFormat::Binary => {
+            if command_line.listing.is_some() {
+                return Err(AssemblerError::without_location("--listing needs a single source file, use --map when linking"));
+            }
+            // archives are searched for members that define the symbols the objects need
+            let (archive_files, object_files): (Vec<&PathBuf>, Vec<&PathBuf>) = inputs
+                .iter()
+                .partition(|path| fs::read_to_string(path).is_ok_and(|text| text.starts_with(ARCHIVE_HEADER)));
+            let mut objects = object_files
+                .iter()
+                .map(|path| read_object(path, &command_line.options))
+                .collect::<Result<Vec<ObjectFile>, AssemblerError>>()?;
+            let archives = archive_files
+                .iter()
+                .map(|path| Archive::read(path))
+                .collect::<Result<Vec<Archive>, AssemblerError>>()?;
+            objects.extend(select_members(&objects, &archives));
+
+            let linked = link(&objects)?;
+            write_binary(&output, &linked.cpu)?;
+            if command_line.verbosity == Verbosity::Verbose {
+                print!("{}", linked.map);
+            }
+            if let Some(map) = &command_line.map {
+                write_text(map, &linked.map)?;
+            }
+            if let Some(sym) = &command_line.sym {
+                let symbols: String = linked
+                    .symbols
+                    .iter()
+                    .map(|(name, address)| format!("{address:#06X} label {name}\n"))
+                    .collect();
+                write_text(sym, &symbols)?;
+            }
+        }
+        OutputFormat::Object => {
+            if inputs.len() != 1 || !is_source(&inputs[0]) {
+                return Err(AssemblerError::without_location("--format obj needs a single source file"));
+            }
+            read_object(&inputs[0], &command_line.options)?.write(&output)?;
+        }
+        OutputFormat::Archive => {
+            let archive = Archive {
+                name: output.display().to_string(),
+                members: inputs
+                    .iter()
+                    .map(|path| read_object(path, &command_line.options))
+                    .collect::<Result<Vec<ObjectFile>, AssemblerError>>()?,
+            };
+            archive.write(&output)?;
+        }
+    }
+    if command_line.verbosity >= Verbosity::Normal {
+        println!("wrote {}", output.display());
+    }
+    Ok(())
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command_line = match CommandLine::parse(&args) {
+        Ok(command_line) => command_line,
+        Err(msg) => {
+            eprintln!("error: {msg}\n\n{USAGE}");
+            process::exit(EXIT_USAGE);
+        }
+    };
+
     let start = Instant::now();
-    if args.len() == 1 {
-        // default compile and run code.cr -> code.bin
-        #[cfg(debug_assertions)]
-        let _ = fs::remove_file("./code.bin");
-        let mut pf = ProgramFile::new("code.cr".into(), "code.bin".into()).unwrap();
-        // if a binary exists, run it, else create one from the code file
-        if File::open("code.bin").is_ok() {
-            println!("Running code.bin");
-            pf.read_binary().unwrap();
-            pf.run_binary();
-        } else {
-            pf.compile();
-            pf.output_binary();
-            pf.read_binary().unwrap();
-            pf.run_binary();
-            #[cfg(debug_assertions)]
-            let _ = fs::remove_file("./code.bin");
-        }
-    } else if args.len() == 2 {
-        // directly run a binary given a filename
-        let binary_file = args.get(1).unwrap();
-        let mut pf = ProgramFile::new_from_binary(binary_file.into()).unwrap();
-        pf.run_binary();
-    } else if args.len() == 3 {
-        // convert source code into a binary
-        let input_file = args.get(1).unwrap();
-        let output_file = args.get(2).unwrap();
-        let mut pf =
-            ProgramFile::new(PathBuf::from(input_file), PathBuf::from(output_file)).unwrap();
-        pf.compile();
-        pf.output_binary();
-    }
-    let end = Instant::now();
-
-    let dur = end.duration_since(start);
-    println!("Compile took {:.2} seconds", dur.as_secs_f32());
+    match command_line.command {
+        Command::Help => println!("{USAGE}"),
+        Command::Build => exit_on_error(build(&command_line)),
+        Command::Run => {
+            let mut pf = exit_on_error(load_program(&command_line));
+            if !pf.run(command_line.max_cycles, command_line.trace) {
+                eprintln!(
+                    "error: program did not finish within {} cycles",
+                    command_line.max_cycles.unwrap_or_default()
+                );
+                process::exit(EXIT_MAX_CYCLES);
+            }
+            if let Some(fault) = pf.fault() {
+                eprintln!("error: fault: {fault}");
+                process::exit(EXIT_FAULT);
+            }
+        }
+        Command::Disasm => {
+            let pf = exit_on_error(load_program(&command_line));
+            for line in pf.disassemble() {
+                println!("{line}");
+            }
+        }
+        Command::Check => {
+            let input = &command_line.inputs[0];
+            let pf = exit_on_error(compile(&command_line, input, command_line.output_path()));
+            let warnings = pf.lint();
+            for warning in &warnings {
+                eprintln!("{warning}");
+            }
+            if !warnings.is_empty() {
+                process::exit(EXIT_WARNINGS);
+            }
+            if command_line.verbosity >= Verbosity::Normal {
+                println!("{}: ok", input.display());
+            }
+        }
+    }
+
+    if command_line.verbosity == Verbosity::Verbose {
+        let dur = Instant::now().duration_since(start);
+        println!("{:?} took {:.2} seconds", command_line.command, dur.as_secs_f32());
+    }
 }
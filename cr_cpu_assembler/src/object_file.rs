@@ -0,0 +1,203 @@
+use crate::error::AssemblerError;
+use crate::layout::Section;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// First line of every object file, followed by the format version
+const OBJECT_HEADER: &str = "CROBJ 1";
+
+/// A symbol defined in an object file, at an offset from the start of one of its sections
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectSymbol {
+    pub name: String,
+    pub section: Section,
+    pub offset: u32,
+    /// Exported symbols, marked with `.global`, can be used by other object files
+    pub exported: bool,
+}
+
+/// A place in a section that holds the address of a symbol, which the linker fills in once the symbol is placed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Relocation {
+    pub section: Section,
+    /// Offset of the word to fill in from the start of the section
+    pub offset: u32,
+    /// Bit shift of the address inside the word
+    pub shift: u32,
+    /// Width of the address inside the word in bits
+    pub width: u32,
+    pub symbol: String,
+}
+
+/// An assembled program that has not been placed in dram yet, to be combined with other object files by the linker
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ObjectFile {
+    /// Name of the source the object was assembled from, used in linker messages
+    pub name: String,
+    pub text: Vec<u32>,
+    pub data: Vec<u32>,
+    /// Number of zero words in the bss section
+    pub bss_size: u32,
+    pub symbols: Vec<ObjectSymbol>,
+    pub relocations: Vec<Relocation>,
+}
+
+impl ObjectFile {
+    /// Number of words a section takes up
+    pub fn section_size(&self, section: Section) -> u32 {
+        match section {
+            Section::Text => self.text.len() as u32,
+            Section::Data => self.data.len() as u32,
+            Section::Bss => self.bss_size,
+        }
+    }
+
+    /// Symbols this object exports for other objects to use
+    pub fn exports(&self) -> impl Iterator<Item = &ObjectSymbol> {
+        self.symbols.iter().filter(|symbol| symbol.exported)
+    }
+
+    /// Symbols this object uses but does not define, which must be exported by another object
+    pub fn imports(&self) -> BTreeSet<&str> {
+        self.relocations
+            .iter()
+            .map(|relocation| relocation.symbol.as_str())
+            .filter(|name| self.find_symbol(name).is_none())
+            .collect()
+    }
+
+    /// Find a symbol defined in this object by name
+    pub fn find_symbol(&self, name: &str) -> Option<&ObjectSymbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    /// Convert the object into its text file format
+    pub fn to_text(&self) -> String {
+        let words = |words: &[u32]| {
+            words
+                .iter()
+                .map(|word| format!("{word:#X}"))
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+
+        let mut text = format!("{OBJECT_HEADER}\nname {}\n", self.name);
+        text += &format!("text {}\n", words(&self.text));
+        text += &format!("data {}\n", words(&self.data));
+        text += &format!("bss {}\n", self.bss_size);
+        for symbol in &self.symbols {
+            text += &format!(
+                "symbol {} {} {} {}\n",
+                symbol.name,
+                symbol.section.name(),
+                symbol.offset,
+                if symbol.exported { "global" } else { "local" }
+            );
+        }
+        for import in self.imports() {
+            text += &format!("import {import}\n");
+        }
+        for relocation in &self.relocations {
+            text += &format!(
+                "reloc {} {} {} {} {}\n",
+                relocation.section.name(),
+                relocation.offset,
+                relocation.shift,
+                relocation.width,
+                relocation.symbol
+            );
+        }
+        text
+    }
+
+    /// Read an object from its text file format
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(OBJECT_HEADER) {
+            return Err("not a cr_cpu object file".to_string());
+        }
+
+        let number = |item: Option<&str>| -> Result<u32, String> {
+            let item = item.ok_or("missing number")?;
+            match item.strip_prefix("0x").or_else(|| item.strip_prefix("0X")) {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => item.parse(),
+            }
+            .map_err(|_| format!("invalid number: {item}"))
+        };
+        let section = |item: Option<&str>| -> Result<Section, String> {
+            let item = item.ok_or("missing section")?;
+            Section::from_name(item).ok_or(format!("invalid section: {item}"))
+        };
+
+        let mut object = ObjectFile::default();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let mut items = rest.split_whitespace();
+            match kind {
+                "name" => object.name = rest.to_string(),
+                "text" => object.text = items.map(|item| number(Some(item))).collect::<Result<_, _>>()?,
+                "data" => object.data = items.map(|item| number(Some(item))).collect::<Result<_, _>>()?,
+                "bss" => object.bss_size = number(items.next())?,
+                "symbol" => object.symbols.push(ObjectSymbol {
+                    name: items.next().ok_or("missing symbol name")?.to_string(),
+                    section: section(items.next())?,
+                    offset: number(items.next())?,
+                    exported: items.next() == Some("global"),
+                }),
+                // imports are only listed for people reading the object, they are found again from the relocations
+                "import" => {}
+                "reloc" => {
+                    let relocation = Relocation {
+                        section: section(items.next())?,
+                        offset: number(items.next())?,
+                        shift: number(items.next())?,
+                        width: number(items.next())?,
+                        symbol: items.next().ok_or("missing relocation symbol")?.to_string(),
+                    };
+                    // the operand being relocated has to fit inside a single 32 bit word
+                    if !(1..=32).contains(&relocation.width) || relocation.shift > 32 - relocation.width {
+                        return Err(format!(
+                            "invalid relocation, a {} bit operand shifted by {} does not fit in a word: {line}",
+                            relocation.width, relocation.shift
+                        ));
+                    }
+                    object.relocations.push(relocation);
+                }
+                _ => return Err(format!("unexpected line in object file: {line}")),
+            }
+        }
+        // a symbol can be at the end of its section, such as a label after the last instruction, but not past it
+        if let Some(symbol) = object
+            .symbols
+            .iter()
+            .find(|symbol| symbol.offset > object.section_size(symbol.section))
+        {
+            return Err(format!(
+                "symbol {} at offset {} is past the end of the {} section, which is {} words",
+                symbol.name,
+                symbol.offset,
+                symbol.section.name(),
+                object.section_size(symbol.section)
+            ));
+        }
+        Ok(object)
+    }
+
+    /// Write the object to a file
+    pub fn write(&self, path: &Path) -> Result<(), AssemblerError> {
+        fs::write(path, self.to_text())
+            .map_err(|err| AssemblerError::without_location(format!("unable to write '{}': {err}", path.display())))
+    }
+
+    /// Read an object from a file
+    pub fn read(path: &Path) -> Result<Self, AssemblerError> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| AssemblerError::without_location(format!("unable to read '{}': {err}", path.display())))?;
+        Self::from_text(&text).map_err(|msg| AssemblerError::without_location(format!("{}: {msg}", path.display())))
+    }
+}
+
+/// Addresses given to each section of each object by the linker, indexed the same as the objects being linked
+pub type SectionAddresses = Vec<HashMap<Section, u32>>;
@@ -0,0 +1,279 @@
+use crate::labels::is_internal_label;
+use crate::program_instruction::ProgramInstruction;
+use crate::program_instruction::ProgramInstruction::{Asm, Global, Label, PreAsm, Variable};
+use crate::source::SourceLocation;
+use cr_cpu_common::constants::{ACC, IR, PC};
+use cr_cpu_common::instruction::Instruction;
+use cr_cpu_common::instruction::Instruction::{
+    Adc, Add, Call, CallR, Cmp, Cmps, Dump, DumpR, IAdc, IAdd, IAddL, ICmp, ICmpL, ICmps, IMoveL, IPush, IPushL, ISbb,
+    ISub, JmpR, Loop, MoveR, Ret, Sbb, Sub,
+};
+
+type Program = Vec<(ProgramInstruction, SourceLocation)>;
+
+/// Optimise a parsed program before layout, used when assembling with `-O`.
+/// Immediates are given their shortest encoding, jumps to the next instruction and redundant moves are removed,
+/// and constant additions to acc are folded together, repeating until nothing else changes.
+/// Rewrites that would change the flags are only made where the flags are dead, see `flags_dead_after`
+pub fn optimise(instructions: Program) -> Program {
+    let mut instructions: Program = instructions
+        .iter()
+        .enumerate()
+        .map(|(index, (inst, location))| match inst {
+            Asm(IMoveL(reg, 0)) if flags_dead_after(&instructions, index) => (Asm(Sub(*reg, *reg)), location.clone()),
+            Asm(inst) => (Asm(shortest_encoding(inst.clone())), location.clone()),
+            other => (other.clone(), location.clone()),
+        })
+        .collect();
+
+    loop {
+        let before = instructions.len();
+        instructions = remove_jumps_to_next(instructions);
+        instructions = remove_redundant_moves(instructions);
+        instructions = fold_constants(instructions);
+        if instructions.len() == before {
+            return instructions;
+        }
+    }
+}
+
+/// Pick the shortest instruction that has the same effect, flags included
+fn shortest_encoding(inst: Instruction) -> Instruction {
+    match inst {
+        ICmpL(reg, value) if value <= u16::MAX as u32 => ICmp(reg, value as u16),
+        IPushL(value) if value <= u16::MAX as u32 => IPush(value as u16),
+        IAddL(value) if value <= u8::MAX as u32 => IAdd(value as u8),
+        other => other,
+    }
+}
+
+/// Remove jumps whose target label comes directly after them, as they would continue to the same place either way.
+/// Labels created by `nop` are kept, since the jump is the point of a `nop`, and so is `loop`, since it also counts down cr
+fn remove_jumps_to_next(instructions: Program) -> Program {
+    let mut kept = Program::with_capacity(instructions.len());
+    for (index, (inst, location)) in instructions.iter().enumerate() {
+        if let PreAsm(jump, target) = inst {
+            let jumps_to_next = jump.jump_target().is_some()
+                && !matches!(jump, Loop(_))
+                && !is_internal_label(target)
+                && instructions[index + 1..]
+                    .iter()
+                    .map(|(inst, _)| inst)
+                    .take_while(|inst| matches!(inst, Label(_) | Global(_)))
+                    .any(|inst| matches!(inst, Label(name) if name == target));
+            if jumps_to_next {
+                continue;
+            }
+        }
+        kept.push((inst.clone(), location.clone()));
+    }
+    kept
+}
+
+/// Remove moves that do not change anything, a move of a register into itself,
+/// a move that is undone by the next move, and a move whose register is overwritten by the next move.
+/// A move sets the zero flag, so a move of a register into itself is only removed when the flags are dead after it
+fn remove_redundant_moves(instructions: Program) -> Program {
+    // moving into pc is a jump, and ir is changed by every fetch, so moves touching them are always kept
+    let plain = |reg: u8| reg != PC && reg != IR;
+
+    let mut kept = Program::with_capacity(instructions.len());
+    for (index, (inst, location)) in instructions.iter().enumerate() {
+        let (inst, location) = (inst.clone(), location.clone());
+        if let Asm(MoveR(to, from)) = inst {
+            if to == from && plain(to) && flags_dead_after(&instructions, index) {
+                continue;
+            }
+            if let Some((Asm(MoveR(previous_to, previous_from)), _)) = kept.last() {
+                let (previous_to, previous_from) = (*previous_to, *previous_from);
+                if plain(to) && plain(from) && plain(previous_to) && plain(previous_from) {
+                    if previous_to == from && previous_from == to {
+                        // move a b, move b a
+                        continue;
+                    }
+                    if previous_to == to && from != to {
+                        // move a b, move a c, a move of a into itself reads the value the previous move left in a
+                        kept.pop();
+                    }
+                }
+            }
+        }
+        kept.push((inst, location));
+    }
+    kept
+}
+
+/// Fold a run of constant additions and subtractions on acc into a single instruction,
+/// folding into a constant move into acc before the run if there is one.
+/// The folded instruction sets different carry, overflow and sign flags, so a run is only folded when the flags are dead after it
+fn fold_constants(instructions: Program) -> Program {
+    let mut kept = Program::with_capacity(instructions.len());
+    for (index, (inst, location)) in instructions.iter().enumerate() {
+        let (inst, location) = (inst.clone(), location.clone());
+        let Some(change) = acc_change(&inst) else {
+            kept.push((inst, location));
+            continue;
+        };
+        if !flags_dead_after(&instructions, index) {
+            kept.push((inst, location));
+            continue;
+        }
+        let folded = match kept.last().map(|(inst, _)| inst) {
+            Some(Asm(IMoveL(ACC, value))) => Some(vec![move_into_acc(value.wrapping_add(change))]),
+            Some(Asm(Sub(ACC, ACC))) => Some(vec![move_into_acc(change)]),
+            Some(previous) => acc_change(previous).map(|previous| add_to_acc(previous.wrapping_add(change)).into_iter().collect()),
+            None => None,
+        };
+        match folded {
+            Some(folded) => {
+                kept.pop();
+                kept.extend(folded.into_iter().map(|inst| (Asm(inst), location.clone())));
+            }
+            None => kept.push((inst, location)),
+        }
+    }
+    kept
+}
+
+/// The constant an instruction adds to acc, subtractions being wrapping additions
+fn acc_change(inst: &ProgramInstruction) -> Option<u32> {
+    match inst {
+        Asm(IAdd(value)) => Some(*value as u32),
+        Asm(IAddL(value)) => Some(*value),
+        Asm(ISub(value)) => Some((*value as u32).wrapping_neg()),
+        _ => None,
+    }
+}
+
+/// The shortest instruction that moves a constant into acc, only used where the flags are dead
+fn move_into_acc(value: u32) -> Instruction {
+    if value == 0 {
+        Sub(ACC, ACC)
+    } else {
+        IMoveL(ACC, value)
+    }
+}
+
+/// The shortest instruction that adds a constant to acc, or nothing if the constant is 0
+fn add_to_acc(value: u32) -> Option<Instruction> {
+    if value == 0 {
+        None
+    } else if value <= u8::MAX as u32 {
+        Some(IAdd(value as u8))
+    } else if value.wrapping_neg() <= u8::MAX as u32 {
+        Some(ISub(value.wrapping_neg() as u8))
+    } else {
+        Some(IAddL(value))
+    }
+}
+
+/// Returns true if no instruction can read the flags left by the instruction at `index` before they are all set again.
+/// The scan follows the program straight on from `index`, so anything that reads flags, moves pc elsewhere, or leaves the
+/// straight line of instructions counts as a read, and the end of the program counts as the flags being dead
+fn flags_dead_after(instructions: &Program, index: usize) -> bool {
+    for (inst, _) in &instructions[index + 1..] {
+        let inst = match inst {
+            Asm(inst) | PreAsm(inst, _) => inst,
+            Label(_) | Global(_) | Variable(_, _) => continue,
+            _ => return false,
+        };
+        match inst {
+            // every jump, call and return might reach code that reads the flags, dump prints them,
+            // and add and subtract with carry read carry
+            _ if inst.jump_target().is_some() => return false,
+            JmpR(_) | Call(_) | CallR(_) | Ret | Dump | DumpR(_) | Adc(_, _) | IAdc(_) | Sbb(_, _) | ISbb(_) => {
+                return false;
+            }
+            // these set the zero, sign, carry and overflow flags without reading them
+            Add(_, _) | IAdd(_) | IAddL(_) | Sub(_, _) | ISub(_) | Cmp(_, _) | ICmp(_, _) | ICmpL(_, _) | Cmps(_, _)
+            | ICmps(_, _) => return true,
+            _ => {}
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cr_cpu_common::constants::{CR, R0};
+    use std::path::PathBuf;
+    use cr_cpu_common::instruction::Instruction::{JMP, JZ};
+
+    /// Optimise a program, leaving out the source locations
+    fn optimised(program: &[ProgramInstruction]) -> Vec<ProgramInstruction> {
+        let program = program
+            .iter()
+            .map(|inst| (
+                    inst.clone(),
+                    SourceLocation {
+                        file: PathBuf::from("test.cr"),
+                        line: 1,
+                    },
+                ))
+            .collect();
+        optimise(program).into_iter().map(|(inst, _)| inst).collect()
+    }
+
+    /// A conditional jump to `end`, which reads the flags left by the instruction before it
+    fn jz_end() -> ProgramInstruction {
+        PreAsm(JZ(0), "end".to_string())
+    }
+
+    #[test]
+    fn long_immediates_that_fit_are_shortened() {
+        let program = [Asm(ICmpL(ACC, 5)), Asm(IPushL(6)), Asm(IAddL(7))];
+        assert_eq!(optimised(&program), [Asm(ICmp(ACC, 5)), Asm(IPush(6)), Asm(IAdd(7))]);
+    }
+
+    #[test]
+    fn move_of_zero_becomes_sub_only_when_the_flags_are_dead() {
+        assert_eq!(optimised(&[Asm(IMoveL(CR, 0))]), [Asm(Sub(CR, CR))]);
+        let program = [Asm(IMoveL(CR, 0)), jz_end(), Asm(Dump), Label("end".to_string())];
+        assert_eq!(optimised(&program), program);
+    }
+
+    #[test]
+    fn jump_to_the_next_instruction_is_removed() {
+        let program = [PreAsm(JMP(0), "next".to_string()), Label("next".to_string()), Asm(Dump)];
+        assert_eq!(optimised(&program), [Label("next".to_string()), Asm(Dump)]);
+    }
+
+    #[test]
+    fn move_into_itself_is_removed_only_when_the_flags_are_dead() {
+        assert_eq!(optimised(&[Asm(MoveR(ACC, ACC))]), []);
+        let program = [Asm(MoveR(ACC, ACC)), jz_end(), Asm(Dump), Label("end".to_string())];
+        assert_eq!(optimised(&program), program);
+    }
+
+    #[test]
+    fn move_undone_by_the_next_move_is_removed() {
+        let program = [Asm(MoveR(ACC, CR)), Asm(MoveR(CR, ACC))];
+        assert_eq!(optimised(&program), [Asm(MoveR(ACC, CR))]);
+    }
+
+    #[test]
+    fn move_overwritten_by_the_next_move_is_removed() {
+        let program = [Asm(MoveR(ACC, CR)), Asm(MoveR(ACC, IR))];
+        assert_eq!(optimised(&program), program, "moves touching ir are kept");
+        let program = [Asm(MoveR(ACC, CR)), Asm(MoveR(ACC, R0))];
+        assert_eq!(optimised(&program), [Asm(MoveR(ACC, R0))]);
+    }
+
+    #[test]
+    fn move_into_itself_keeps_the_move_before_it() {
+        // move acc cr, move acc acc, jz end, the move into itself is kept for the flags and reads what cr moved into acc
+        let program = [Asm(MoveR(ACC, CR)), Asm(MoveR(ACC, ACC)), jz_end(), Asm(Dump), Label("end".to_string())];
+        assert_eq!(optimised(&program), program);
+    }
+
+    #[test]
+    fn constant_additions_are_folded_when_the_flags_are_dead() {
+        let program = [Asm(IMoveL(ACC, 7)), Asm(IAdd(3)), Asm(ISub(2))];
+        assert_eq!(optimised(&program), [Asm(IMoveL(ACC, 8))]);
+        let program = [Asm(IAdd(3)), Asm(ISub(3)), Asm(Dump)];
+        assert_eq!(optimised(&program), [Asm(IAdd(3)), Asm(ISub(3)), Asm(Dump)]);
+        let program = [Asm(IAdd(200)), Asm(IAdd(100))];
+        assert_eq!(optimised(&program), [Asm(IAddL(300))]);
+    }
+}
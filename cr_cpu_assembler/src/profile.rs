@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+
+/// What the program is being built for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Profile {
+    /// Keeps dump instructions and `.ifdef DEBUG` blocks, and writes a source line map to a `.dbg` file next to the binary
+    #[default]
+    Debug,
+    /// Leaves out dump instructions and `.ifdef DEBUG` blocks
+    Release,
+}
+
+impl Profile {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "debug" => Some(Profile::Debug),
+            "release" => Some(Profile::Release),
+            _ => None,
+        }
+    }
+
+    /// Constants that are defined when building with this profile, debug builds define `DEBUG` as 1
+    pub fn constants(&self) -> HashMap<String, u32> {
+        match self {
+            Profile::Debug => HashMap::from([("DEBUG".to_string(), 1)]),
+            Profile::Release => HashMap::new(),
+        }
+    }
+}
@@ -1,43 +1,72 @@
+use crate::directive::{is_directive, parse_directive};
+use crate::error::AssemblerError;
+use crate::labels::{is_label, LabelScope};
+use crate::layout::{layout_program, Region, Section};
+use crate::lint::{lint, PlacedInstruction, Warning};
+use crate::object_file::{ObjectFile, ObjectSymbol, Relocation};
+use crate::optimise::optimise;
+use crate::profile::Profile;
 use crate::program_instruction::ProgramInstruction;
+use crate::pseudo::{expand_pseudo, is_pseudo_instruction};
 use crate::program_instruction::ProgramInstruction::*;
+use crate::source::{load_source, SourceLine, SourceLocation};
+use cr_cpu_common::debug_info::{DebugInfo, LabelRange, SourceInfo};
 use cr_cpu_common::instruction::Instruction;
 use cr_cpu_common::prelude::Cpu;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io;
-use std::io::{Read, Write};
-use std::path::PathBuf;
-use cr_cpu_common::constants::SP;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use cr_cpu_common::constants::{get_id_from_reg_name, SP};
 use cr_cpu_common::instruction::Instruction::IMoveL;
 use cr_cpu_common::PCReference;
 
 /// ProgramFile represents a single file of assembly that can be built into a cpu struct
 pub struct ProgramFile {
-    lines: Vec<String>,
+    /// Path of the source file, or binary, the program was read from
+    path: PathBuf,
+    /// Every line of source code, with all included files already read in
+    lines: Vec<SourceLine>,
     /// Label is a named line number
     labels: HashMap<String, PCReference>,
     variables: HashMap<String, PCReference>,
     output_path: PathBuf,
     cpu: Cpu,
+    options: AssemblerOptions,
+    /// Text listing of where everything was placed and what it was assembled into, filled in by `compile`
+    listing: String,
+    /// Every program instruction with its address, and the stack pointer setup before them, filled in by `compile`
+    placed: Vec<PlacedInstruction>,
+    prologue: Option<Instruction>,
 }
 
-
-
-// TODO: compiler profiles, release mode skips compiling all dump instructions ? debug mode does not
+/// Settings that change how a program is assembled
+#[derive(Clone, Debug, Default)]
+pub struct AssemblerOptions {
+    /// Run the optimisation pass over the program before it is laid out
+    pub optimise: bool,
+    pub profile: Profile,
+    /// Constants defined on the command line with `-D NAME=value`, for conditional assembly
+    pub definitions: HashMap<String, u32>,
+}
 
 impl ProgramFile {
-    /// Create a new program file from a source code path, and an output path
-    pub fn new(path: PathBuf, output_path: PathBuf) -> Result<Self, io::Error> {
+    /// Create a new program file from a source code path, an output path, and the options it is assembled with.
+    /// Any files included by the source file are read in as well, conditional assembly is done while the files are read,
+    /// so the options are needed to know which constants are defined
+    pub fn new(path: PathBuf, output_path: PathBuf, options: AssemblerOptions) -> Result<Self, AssemblerError> {
+        let mut constants = options.profile.constants();
+        constants.extend(options.definitions.clone());
         Ok(Self {
-            lines: {
-                let mut s = String::new();
-                File::open(path)?.read_to_string(&mut s)?;
-                s.split('\n').map(|line| line.to_string()).collect()
-            },
+            lines: load_source(&path, constants)?,
+            path,
             labels: HashMap::new(),
             variables: Default::default(),
             output_path,
             cpu: Cpu::new(),
+            options,
+            listing: String::new(),
+            placed: vec![],
+            prologue: None,
         })
     }
 
@@ -48,217 +77,482 @@ impl ProgramFile {
             labels: Default::default(),
             variables: Default::default(),
             output_path: path.clone(),
-            cpu: Cpu::from_binary(path)?,
+            cpu: Cpu::from_binary(path.clone())?,
+            path,
+            options: AssemblerOptions::default(),
+            listing: String::new(),
+            placed: vec![],
+            prologue: None,
         })
     }
 
-    /// Run the stored binary from the output path
-    pub fn run_binary(&mut self) {
-        self.run();
-    }
+    /// Read every source line into a list of program instructions, remembering the line each one came from
+    fn parse(&self) -> Result<Vec<(ProgramInstruction, SourceLocation)>, AssemblerError> {
+        let mut instructions: Vec<(ProgramInstruction, SourceLocation)> = vec![];
+        let mut scope = LabelScope::default();
 
-    /// Read the binary stored in output path,
-    /// parse it, and modify the cpu struct inside the program file
-    pub fn read_binary(&mut self) -> Result<(), io::Error> {
-        self.cpu = Cpu::from_binary(self.output_path.clone())?;
-        Ok(())
+        // iterate through every program line, skipping lines that are empty or only contain a comment
+        for source_line in &self.lines {
+            let line = source_line.tokens()?;
+            if line.is_empty() {
+                continue;
+            }
+            let location = source_line.location.clone();
+
+            if let Some(inst) = Instruction::from_code_line(&line) {
+                // release builds leave out dump instructions
+                if self.options.profile == Profile::Release && matches!(inst, Instruction::Dump | Instruction::DumpR(_)) {
+                    continue;
+                }
+                // add an instruction to the compiler list so we can compile it later
+                instructions.push((Asm(inst), location));
+            } else if is_pseudo_instruction(&line[0]) {
+                // pseudo-instructions are expanded now, so layout sees the real instructions they stand for
+                let expansion = expand_pseudo(&line, &location, &mut scope)?;
+                instructions.extend(expansion.into_iter().map(|inst| (inst, location.clone())));
+            } else if is_label(&line[0]) {
+                // if a given line is a label, add it as an instruction to the list, so we can count it later
+                let name = scope.define(&line[0], &location);
+                instructions.push((Label(name), location));
+            } else if is_directive(&line[0]) {
+                let mut directive = parse_directive(&line).map_err(|msg| AssemblerError::new(&location, msg))?;
+                if let Addresses(names) = &mut directive {
+                    for name in names.iter_mut() {
+                        *name = scope.reference(name, &location)?;
+                    }
+                }
+                instructions.push((directive, location));
+            } else if line.get(1).is_some_and(|item| item == "=") {
+                // negative values are stored as two's complement
+                let value: u32 = line
+                    .get(2)
+                    .and_then(|value| value.parse().ok().or_else(|| value.parse::<i32>().ok().map(|value| value as u32)))
+                    .ok_or_else(|| AssemblerError::new(&location, format!("variable {} needs a number value", line[0])))?;
+                instructions.push((Variable(line[0].to_string(), value), location));
+            } else if let Some((inst, symbol)) = is_precompile_label_inst(&line) {
+                // PreAsm is an instruction that represents another instruction that is going to be formed by the compiler
+                // once the address of the label or variable it names is known
+                let symbol = scope.reference(&symbol, &location)?;
+                instructions.push((PreAsm(inst, symbol), location));
+            } else {
+                // all other checks failed, meaning we dont know what this line is supposed to mean
+                return Err(AssemblerError::new(&location, format!("unexpected item in line: {:?}", line)));
+            }
+        }
+
+        scope.finish()?;
+        if self.options.optimise {
+            return Ok(optimise(instructions));
+        }
+        Ok(instructions)
     }
 
     /// Convert the stored input file data into a cpu struct, and store the cpu struct in self
-    pub fn compile(&mut self) {
+    /// Returns an error pointing at the offending source line if the program can not be assembled
+    pub fn compile(&mut self) -> Result<(), AssemblerError> {
         self.cpu = Cpu::new();
-        let mut instructions: Vec<ProgramInstruction> = vec![];
-
-        // local function to determine the number of added lines given multiline instructions
-        let added_lines = |list: &Vec<ProgramInstruction>, var_list_len: usize| -> u32 {
-            list.iter()
-                .filter_map(|inst| match inst {
-                    Asm(inst) => Some(inst),
-                    _ => None,
-                })
-                .map(|inst| inst.to_instruction_data().len() as u32)
-                .filter(|len| *len > 1)
-                .map(|len| len - 1)
-                .sum::<u32>() + var_list_len as u32
-        };
+        self.labels.clear();
+        self.variables.clear();
+        self.listing.clear();
+        let instructions = self.parse()?;
 
-        // iterate through every program line, filtering as needed
-        for (line_index, line) in self
-            .lines
-            .iter()
-            .filter(|line| !line.is_empty() && !line.trim().starts_with(';'))
-            .enumerate()
-            .map(|(index, line)| {
-                (
-                    index,
-                    line.split_whitespace()
-                        .map(|item| item.to_string())
-                        .take_while(|item| !item.contains(';'))
-                        .collect::<Vec<String>>(),
-                )
-            })
-        {
-            let inst_opt = Instruction::from_code_line(
-                &line,
-                added_lines(&instructions[0..line_index].to_vec(), self.variables.len()),
-                &self.variables,
-            );
-            if let Some(inst) = inst_opt {
-                // add an instruction to the compiler list so we can compile it later
-                instructions.push(Asm(inst));
-            } else if is_label(line.get(0).unwrap()) {
-                // if a given line is a label, add it as an instruction to the list, so we can count it later
-                instructions.push(Label(line.get(0).unwrap().to_string()));
-            } else if line.get(1).unwrap().eq("=") {
-                instructions.push(Variable(
-                    line.get(0).unwrap().to_string(),
-                    line.get(2).unwrap().parse().unwrap(),
-                ));
-                let location = self.cpu.push_variable(line.get(2).unwrap().parse::<u32>().unwrap());
-                self.variables.insert(line.get(0).unwrap().to_string(),PCReference(location));
-            } else {
-                match (line.get(0), line.get(1)) {
-                    // instructions that require two items that are precompiler friendly
-                    (Some(l1), Some(l2)) => {
-                        if let Some((inst, label)) = is_precompile_label_inst(l1, l2) {
-                            // PreAsm is an instruction that represents another instruction that is going to be formed by the compiler
-                            // at the moment, a jump instruction that contains a label will become a preasm instruction
-                            instructions.push(PreAsm(inst, label));
-                        } else {
-                            panic!("Unexpected item in line {}: {:?}", line_index + 1, line);
-                        }
-                    }
-                    (_, _) => {
-                        // the line was not a jump instruction, and all other checks failed, meaning we dont know what this line is supposed to mean
-                        panic!("Unexpected item in line {}: {:?}", line_index + 1, line);
-                    }
+        // variables are stored at the start of the stack area
+        for (inst, location) in &instructions {
+            if let Variable(name, value) = inst {
+                let address = self.cpu.push_variable(*value);
+                if self.variables.insert(name.to_string(), PCReference(address)).is_some() {
+                    return Err(AssemblerError::new(location, format!("variable {name} is defined more than once")));
                 }
             }
         }
 
-        // label pass on instructions to add labels from instruction list into label memory for the compiler
-        {
-            // instruction index that a label will take
-            let mut inst_index = 0;
-            for inst in instructions.iter() {
-                match inst {
-                    Asm(_) => {
-                        inst_index += 1;
-                    }
-                    Label(label) => {
-                        // labels do not increment the instruction index for memory, as we dont want them to influence line numbering
-                        self.labels
-                            .insert(label.to_string().replace(':', ""), PCReference(inst_index));
-                    }
-                    PreAsm(_, _) => {
-                        inst_index += 1;
+        // if there are any variables, the program starts by moving the stack pointer past them
+        let variable_count = self.variables.len() as u32;
+        let prologue = (variable_count > 0).then(|| IMoveL(SP, Cpu::default().get_sp() + variable_count));
+
+        // label pass, giving every instruction an address, and adding labels into label memory for the compiler
+        let (addresses, sections) = {
+            let prologue_len = prologue
+                .as_ref()
+                .map(|inst| inst.to_instruction_data().len() as u32)
+                .unwrap_or(0);
+            let variables_start = Cpu::default().get_sp();
+            let reserved = vec![
+                Region {
+                    name: "stack pointer setup".to_string(),
+                    start: 0,
+                    end: prologue_len,
+                    location: None,
+                },
+                Region {
+                    name: "variables".to_string(),
+                    start: variables_start,
+                    end: variables_start + variable_count,
+                    location: None,
+                },
+            ];
+            let layout = layout_program(&instructions, prologue_len, reserved)?;
+
+            for ((inst, location), address) in instructions.iter().zip(&layout.addresses) {
+                if let Label(name) = inst {
+                    if self.variables.contains_key(name) {
+                        return Err(AssemblerError::new(location, format!("label {name} has the same name as a variable")));
                     }
-                    Variable(_,_) => {
-                        // nothing to do here at the moment
+                    if self.labels.insert(name.clone(), PCReference(*address)).is_some() {
+                        return Err(AssemblerError::new(location, format!("label {name} is defined more than once")));
                     }
                 }
             }
-        }
+            for region in layout.regions.iter().filter(|region| region.start < region.end) {
+                self.listing += &format!("REGION {} [{}..{}]\n", region.name, region.start, region.end);
+            }
+            (layout.addresses, layout.sections)
+        };
+        self.placed = instructions
+            .iter()
+            .zip(&addresses)
+            .zip(sections)
+            .map(|(((inst, location), address), section)| PlacedInstruction {
+                inst: inst.clone(),
+                location: location.clone(),
+                address: *address,
+                section,
+            })
+            .collect();
 
-        // final compilation of adding the cpu instructions to the list, changing preasm into the intended instruction
+        // final compilation of adding the cpu instructions to dram at their address, changing preasm into the intended instruction
         {
-            let hex_text = |inst: &Instruction| -> String {
-                inst.to_instruction_data()
-                    .iter()
-                    .fold("".to_string(), |a, b| format!("{a} {b:#X}"))
-            };
+            if let Some(prologue) = &prologue {
+                self.listing += &format!("{0:?} : {1}\n", prologue, hex_text(&prologue.to_instruction_data()));
+                self.cpu.write_dram(0, &prologue.to_instruction_data());
+            }
+            self.prologue = prologue;
 
-            let variable_count = self.variables.len() as u32;
-            let starting_sp = Cpu::default().get_sp() + variable_count;
-            if variable_count > 0 {
-                self.cpu.add_to_end(&IMoveL(SP,starting_sp));
+            // debug builds remember where every instruction came from, so it can be shown while running
+            if self.options.profile == Profile::Debug {
+                self.cpu.set_debug_info(self.debug_info(&instructions, &addresses));
             }
 
             // final pass on instructions, adding them as needed to the cpu dram.
-            for (_, inst) in instructions.clone().into_iter().enumerate() {
+            for ((inst, location), address) in instructions.iter().cloned().zip(addresses) {
                 match inst {
                     Asm(inst) => {
-                        println!("{0:?} : {1}", inst, hex_text(&inst));
-                        // long instructions that have the secondary argument of 0 get replaced since 0 is an empty line
-                        self.cpu.add_to_end(&inst);
+                        self.listing += &format!("{0:?} : {1}\n", inst, hex_text(&inst.to_instruction_data()));
+                        self.cpu.write_dram(address, &inst.to_instruction_data());
                     }
-                    PreAsm(mut inst_precomp, inst_label) => {
-                        match inst_precomp {
-                            Instruction::JMP(_)
-                            | Instruction::JE(_)
-                            | Instruction::JGT(_)
-                            | Instruction::JLT(_)
-                            | Instruction::JZ(_)
-                            | Instruction::JOV(_) => {
-                                let label_line_num = self.labels.get(inst_label.as_str()).unwrap().0 as u16;
-                                // only consider the line numbers preceding the label to check for added lines
-                                let final_added_lines = added_lines(
-                                    &instructions.as_slice()[0..(label_line_num as usize)].to_vec(),self.variables.len()
-                                ) as u16;
-                                // changing this to allow for other assembly instructions to be considered preasm would probably require
-                                // checking the instruction type first
-                                inst_precomp.change_jump_line(label_line_num + final_added_lines);
-                                self.cpu.add_to_end(&inst_precomp);
-                                println!("{0:?} : {1}", inst_precomp, hex_text(&inst_precomp));
-                            }
-                            _ => {}
-                        }
+                    PreAsm(mut inst_precomp, symbol) => {
+                        // every label and variable has an address by now, so forward references can be filled in
+                        let symbol_address = self.symbol_address(&symbol, &location)?;
+                        inst_precomp.set_address_operand(symbol_address).ok_or_else(|| {
+                            AssemblerError::new(&location, format!("address of {symbol} ({symbol_address}) does not fit in {inst_precomp:?}"))
+                        })?;
+                        self.cpu.write_dram(address, &inst_precomp.to_instruction_data());
+                        self.listing += &format!("{0:?} : {1}\n", inst_precomp, hex_text(&inst_precomp.to_instruction_data()));
                     }
-                    Label(label_text) => {
-                        println!("LABEL: \'{label_text}\'");
+                    Data(words) => {
+                        self.listing += &format!("DATA [{address}] : {}\n", hex_text(&words));
+                        self.cpu.write_dram(address, &words);
                     }
-                    Variable(name,val) => {
-                        println!("Variable: {name} : {val}");
+                    Addresses(names) => {
+                        let words = names
+                            .iter()
+                            .map(|name| self.symbol_address(name, &location))
+                            .collect::<Result<Vec<u32>, AssemblerError>>()?;
+                        self.listing += &format!("ADDR [{address}] {} : {}\n", names.join(" "), hex_text(&words));
+                        self.cpu.write_dram(address, &words);
                     }
+                    other => self.listing += &listing_item(&other, address),
                 }
             }
         }
 
-        // extra whitespace at the end just for you :)
-        println!();
+        Ok(())
     }
 
-    /// Runs the program stored in the cpu
-    pub fn run(&mut self) {
-        self.cpu.execute_until_unknown();
+    /// Address of a label or variable, every label and variable has an address once the label pass is done
+    fn symbol_address(&self, symbol: &str, location: &SourceLocation) -> Result<u32, AssemblerError> {
+        self.labels
+            .get(symbol)
+            .or_else(|| self.variables.get(symbol))
+            .map(|address| address.0)
+            .ok_or_else(|| AssemblerError::new(location, format!("unknown label or variable: {symbol}")))
     }
 
-    /// Outputs a binary to the output path within self
-    pub fn output_binary(&self) {
-        let mut file = File::create(&self.output_path).unwrap();
-        for inst in self.cpu.get_dram() {
-            let bytes = inst.to_le_bytes().to_vec();
-            let _ = file.write(bytes.as_slice()).unwrap();
+    /// Collect the source line of every instruction, the scope of every label, and the address of every variable.
+    /// A label's scope runs until the next label, global labels skip over the local labels inside them
+    fn debug_info(&self, instructions: &[(ProgramInstruction, SourceLocation)], addresses: &[u32]) -> DebugInfo {
+        let mut info = DebugInfo::default();
+        let source_text: HashMap<&SourceLocation, &str> =
+            self.lines.iter().map(|line| (&line.location, line.text.as_str())).collect();
+        for ((inst, location), address) in instructions.iter().zip(addresses) {
+            if let Asm(_) | PreAsm(_, _) = inst {
+                let text = source_text.get(location).copied().unwrap_or_default();
+                let column = text.chars().take_while(|c| c.is_whitespace()).count() + 1;
+                info.lines.insert(
+                    *address,
+                    SourceInfo {
+                        file: location.file.display().to_string(),
+                        line: location.line,
+                        column,
+                        text: text.trim().to_string(),
+                    },
+                );
+            }
+        }
+
+        let program_end = instructions
+            .iter()
+            .zip(addresses)
+            .map(|((inst, _), address)| address + inst.size_at(*address))
+            .max()
+            .unwrap_or(0);
+        // labels made up by the assembler, such as anonymous labels, do not start a scope
+        let mut labels: Vec<(&String, u32)> = self
+            .labels
+            .iter()
+            .filter(|(name, _)| !name.starts_with('@'))
+            .map(|(name, address)| (name, address.0))
+            .collect();
+        labels.sort_by_key(|(name, address)| (*address, name.contains('.'), *name));
+        for (name, start) in &labels {
+            let is_global = |name: &str| !name.contains('.');
+            let end = labels
+                .iter()
+                .filter(|(other, other_start)| other_start > start && (!is_global(name) || is_global(other)))
+                .map(|(_, other_start)| *other_start)
+                .min()
+                .unwrap_or(program_end.max(*start + 1));
+            info.labels.push(LabelRange {
+                name: name.to_string(),
+                start: *start,
+                end,
+            });
         }
+
+        info.variables = self
+            .variables
+            .iter()
+            .map(|(name, address)| (name.clone(), address.0))
+            .collect();
+        info
+    }
+
+    /// Assemble the program into a relocatable object file, which is placed in dram later by the linker.
+    /// Variables are stored in the data section, and every use of a label or variable becomes a relocation
+    pub fn assemble_object(&self) -> Result<ObjectFile, AssemblerError> {
+        let mut instructions = vec![];
+        for (inst, location) in self.parse()? {
+            match inst {
+                Org(_) | SectionStart(_, Some(_)) => {
+                    return Err(AssemblerError::new(
+                        &location,
+                        "object files are placed by the linker, so they can not use .org or section base addresses",
+                    ));
+                }
+                Variable(name, value) => {
+                    // store the variable in the data section, then go back to the section we were in
+                    let current = instructions
+                        .iter()
+                        .rev()
+                        .find_map(|(inst, _)| match inst {
+                            SectionStart(section, _) => Some(*section),
+                            _ => None,
+                        })
+                        .unwrap_or(Section::Text);
+                    instructions.push((SectionStart(Section::Data, None), location.clone()));
+                    instructions.push((Label(name), location.clone()));
+                    instructions.push((Data(vec![value]), location.clone()));
+                    instructions.push((SectionStart(current, None), location));
+                }
+                inst => instructions.push((inst, location)),
+            }
+        }
+
+        let layout = layout_program(&instructions, 0, vec![])?;
+        let offset = |index: usize| layout.addresses[index] - layout.section_starts[&layout.sections[index]];
+
+        let mut object = ObjectFile {
+            name: self.path.display().to_string(),
+            ..Default::default()
+        };
+
+        for (index, (inst, location)) in instructions.iter().enumerate() {
+            if let Label(name) = inst {
+                if object.find_symbol(name).is_some() {
+                    return Err(AssemblerError::new(location, format!("label {name} is defined more than once")));
+                }
+                object.symbols.push(ObjectSymbol {
+                    name: name.clone(),
+                    section: layout.sections[index],
+                    offset: offset(index),
+                    exported: false,
+                });
+            }
+        }
+        for (inst, location) in &instructions {
+            if let Global(name) = inst {
+                let symbol = object
+                    .symbols
+                    .iter_mut()
+                    .find(|symbol| symbol.name == *name)
+                    .ok_or_else(|| AssemblerError::new(location, format!("can not export {name}, it is not defined")))?;
+                symbol.exported = true;
+            }
+        }
+
+        for (index, (inst, _)) in instructions.iter().enumerate() {
+            let section = layout.sections[index];
+            let words = match inst {
+                Asm(inst) => inst.to_instruction_data(),
+                PreAsm(inst, symbol) => {
+                    let (word, shift, width) = inst.address_operand_position().unwrap();
+                    object.relocations.push(Relocation {
+                        section,
+                        offset: offset(index) + word as u32,
+                        shift,
+                        width,
+                        symbol: symbol.clone(),
+                    });
+                    let mut inst = inst.clone();
+                    inst.set_address_operand(0);
+                    inst.to_instruction_data()
+                }
+                Data(words) => words.clone(),
+                Addresses(names) => {
+                    for (word, name) in names.iter().enumerate() {
+                        object.relocations.push(Relocation {
+                            section,
+                            offset: offset(index) + word as u32,
+                            shift: 0,
+                            width: 32,
+                            symbol: name.clone(),
+                        });
+                    }
+                    vec![0; names.len()]
+                }
+                Align(_) => vec![0; inst.size_at(layout.addresses[index]) as usize],
+                _ => vec![],
+            };
+            match section {
+                Section::Text => object.text.extend(words),
+                Section::Data => object.data.extend(words),
+                Section::Bss => object.bss_size += words.len() as u32,
+            }
+        }
+
+        Ok(object)
+    }
+
+    /// Runs the program stored in the cpu, printing every instruction executed if `trace` is set.
+    /// Returns false if the program was still running after `max_cycles` instructions
+    pub fn run(&mut self, max_cycles: Option<usize>, trace: bool) -> bool {
+        self.cpu.set_trace(trace);
+        self.cpu.execute_until_unknown_or_limit(max_cycles)
+    }
+
+    /// The fault that stopped the last run, described with the source line of the faulting instruction if it is known
+    pub fn fault(&self) -> Option<String> {
+        let fault = self.cpu.get_fault()?;
+        Some(match self.cpu.get_debug_info().annotate(fault.address()) {
+            Some(annotation) => format!("{fault}, {annotation}"),
+            None => fault.to_string(),
+        })
+    }
+
+    /// Look for code in the last compiled program that is probably not what was meant
+    pub fn lint(&self) -> Vec<Warning> {
+        lint(&self.placed, &self.labels, &self.variables, self.prologue.as_ref())
+    }
+
+    /// The compile listing of the last call to `compile`
+    pub fn listing(&self) -> &str {
+        &self.listing
+    }
+
+    /// Every label and variable with its address, one per line sorted by address, e.g. `0x0004 label main.loop`
+    pub fn symbol_table(&self) -> String {
+        let mut symbols: Vec<(u32, &str, &String)> = self
+            .labels
+            .iter()
+            .map(|(name, address)| (address.0, "label", name))
+            .chain(self.variables.iter().map(|(name, address)| (address.0, "variable", name)))
+            .collect();
+        symbols.sort();
+        symbols
+            .iter()
+            .map(|(address, kind, name)| format!("{address:#06X} {kind} {name}\n"))
+            .collect()
+    }
+
+    /// Disassemble the program stored in the cpu
+    pub fn disassemble(&self) -> Vec<String> {
+        self.cpu.disassemble()
+    }
+
+    /// Outputs a binary to the output path within self, along with its debug info, see `write_binary`
+    pub fn output_binary(&self) -> Result<(), AssemblerError> {
+        write_binary(&self.output_path, &self.cpu)
     }
 }
 
-/// Returns true if the given item is a label, requirements being that it starts and ends with ':'
-/// e.g. `:this_is_a_label:`
-fn is_label(item: &str) -> bool {
-    item.starts_with(':') && item.ends_with(':')
+/// Write the dram of a cpu to a binary at `path`, and its debug info to a `.dbg` file next to it.
+/// A `.dbg` file left by an earlier build is removed if the cpu has no debug info, so it is not read with the new binary
+pub fn write_binary(path: &Path, cpu: &Cpu) -> Result<(), AssemblerError> {
+    let write_error =
+        |path: &Path, err: io::Error| AssemblerError::without_location(format!("unable to write '{}': {err}", path.display()));
+    fs::write(path, cpu.to_binary()).map_err(|err| write_error(path, err))?;
+    let debug_info = cpu.get_debug_info();
+    let debug_info_path = path.with_extension("dbg");
+    if !debug_info.is_empty() {
+        debug_info.write(&debug_info_path).map_err(|err| write_error(&debug_info_path, err))?;
+    } else if debug_info_path.exists() {
+        fs::remove_file(&debug_info_path).map_err(|err| write_error(&debug_info_path, err))?;
+    }
+    Ok(())
 }
 
-/// Returns true if a given line and secondary line item is a valid instruction, outputs the label if so.
-/// This function is to be used to write pre-compiler instructions
-fn is_precompile_label_inst(item: &str, label: &str) -> Option<(Instruction, String)> {
-    // we add code line 1000 as a temporary value, since we overwrite it later in compilation anyway.
-    // we also use 0 added lines, since that will also be overwritten
-    let inst = Instruction::from_code_line(&vec![item.to_string(), "1000".to_string()], 0,&HashMap::default())?;
+/// Format instruction data as hex for the compile listing
+fn hex_text(data: &[u32]) -> String {
+    data.iter()
+        .fold("".to_string(), |a, b| format!("{a} {b:#X}"))
+}
 
+/// Describe a program instruction that does not put any data in dram as a line of the compile listing
+fn listing_item(inst: &ProgramInstruction, address: u32) -> String {
     match inst {
-        Instruction::JMP(_)
-        | Instruction::JE(_)
-        | Instruction::JGT(_)
-        | Instruction::JLT(_)
-        | Instruction::JZ(_)
-        | Instruction::JOV(_) => {
-            // do nothing, since the instruction is as expected!
-        }
-        _ => {
-            return None;
-        }
+        Label(label_text) => format!("LABEL: '{label_text}'\n"),
+        Variable(name, val) => format!("Variable: {name} : {val}\n"),
+        Align(alignment) => format!("ALIGN {alignment}\n"),
+        Org(_) => format!("ORG {address}\n"),
+        SectionStart(section, _) => format!("SECTION {} [{address}]\n", section.name()),
+        Global(name) => format!("GLOBAL {name}\n"),
+        Asm(_) | PreAsm(_, _) | Data(_) | Addresses(_) => String::new(),
     }
+}
 
-    Some((inst, label.to_string()))
+/// Returns the instruction and the name it uses if the line is an instruction with a label or variable in place of an address or number.
+/// This function is to be used to write pre-compiler instructions
+fn is_precompile_label_inst(line: &[String]) -> Option<(Instruction, String)> {
+    let symbol_index = line
+        .iter()
+        .skip(1)
+        .position(|item| is_symbol_name(item) && get_id_from_reg_name(item).is_none())?
+        + 1;
+
+    // we use 1000 as a temporary value, since we overwrite it later in compilation anyway.
+    let mut line = line.to_vec();
+    let symbol = std::mem::replace(&mut line[symbol_index], "1000".to_string());
+    let inst = Instruction::from_code_line(&line)?;
+
+    inst.has_address_operand().then_some((inst, symbol))
+}
+
+/// Returns true if an instruction argument is a name rather than a number,
+/// e.g. `loop`, `_start`, `.local`, or an anonymous label reference such as `-` or `++`
+pub fn is_symbol_name(item: &str) -> bool {
+    let anonymous = item.chars().all(|c| c == '-') || item.chars().all(|c| c == '+');
+    match item.chars().next() {
+        Some(c) => anonymous || c.is_alphabetic() || c == '_' || c == '.' || c == '@',
+        None => false,
+    }
 }
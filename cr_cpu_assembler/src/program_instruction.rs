@@ -1,6 +1,7 @@
+use crate::layout::Section;
 use cr_cpu_common::instruction::Instruction;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ProgramInstruction {
     /// An assembly instruction
     Asm(Instruction),
@@ -10,6 +11,37 @@ pub enum ProgramInstruction {
     /// A label definition
     Label(String),
     Variable(String, u32),
+    /// Raw words placed directly into the program, created by data directives such as `.word` and `.string`
+    Data(Vec<u32>),
+    /// Words holding the addresses of labels or variables, created by `.addr` for jump tables and function pointers
+    Addresses(Vec<String>),
+    /// Pads the program with zeros until the next address is a multiple of the given number of words
+    Align(u32),
+    /// Moves the location counter of the current section to an address
+    Org(u32),
+    /// Starts placing the following items into a section, optionally setting the base address of the section
+    SectionStart(Section, Option<u32>),
+    /// Marks a label as exported from an object file, so other object files can use it
+    Global(String),
+}
+
+impl ProgramInstruction {
+    /// Number of dram words this item takes up when placed at the given address
+    pub fn size_at(&self, address: u32) -> u32 {
+        match self {
+            ProgramInstruction::Asm(inst) | ProgramInstruction::PreAsm(inst, _) => {
+                inst.to_instruction_data().len() as u32
+            }
+            ProgramInstruction::Data(words) => words.len() as u32,
+            ProgramInstruction::Addresses(names) => names.len() as u32,
+            ProgramInstruction::Align(alignment) => address.next_multiple_of(*alignment) - address,
+            ProgramInstruction::Label(_)
+            | ProgramInstruction::Variable(_, _)
+            | ProgramInstruction::Org(_)
+            | ProgramInstruction::SectionStart(_, _)
+            | ProgramInstruction::Global(_) => 0,
+        }
+    }
 }
 
 // TODO: add a new program instruction, called variable
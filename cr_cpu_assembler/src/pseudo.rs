@@ -0,0 +1,72 @@
+use crate::directive::parse_word;
+use crate::error::AssemblerError;
+use crate::labels::LabelScope;
+use crate::program_file::is_symbol_name;
+use crate::program_instruction::ProgramInstruction;
+use crate::program_instruction::ProgramInstruction::{Asm, Label, PreAsm};
+use crate::source::SourceLocation;
+use cr_cpu_common::constants::{get_id_from_reg_name, TR};
+use cr_cpu_common::instruction::Instruction::{IMoveL, MoveR, Sub, JMP};
+
+/// Names of every pseudo-instruction, instructions the assembler expands into one or more real instructions
+const PSEUDO_INSTRUCTIONS: [&str; 4] = ["clr", "mov", "neg", "nop"];
+
+/// Returns true if the given item is the name of a pseudo-instruction
+/// e.g. `clr` or `nop`
+pub fn is_pseudo_instruction(item: &str) -> bool {
+    PSEUDO_INSTRUCTIONS.contains(&item.to_lowercase().as_str())
+}
+
+/// Expand a pseudo-instruction line into the real instructions it stands for.
+/// This happens before layout, so every expanded instruction takes up its real size when labels are given addresses.
+/// `tr` is used as a scratch register by some expansions, it has no name in assembly so programs can not depend on it
+pub fn expand_pseudo(
+    line: &[String],
+    location: &SourceLocation,
+    scope: &mut LabelScope,
+) -> Result<Vec<ProgramInstruction>, AssemblerError> {
+    let name = line[0].to_lowercase();
+    let error = |msg: String| AssemblerError::new(location, msg);
+    let register = |item: &str| {
+        get_id_from_reg_name(item).ok_or_else(|| error(format!("{name} expects a register, found {item}")))
+    };
+
+    let expansion = match (name.as_str(), &line[1..]) {
+        // clr reg = sub reg reg
+        ("clr", [reg]) => {
+            let reg = register(reg)?;
+            vec![Asm(Sub(reg, reg))]
+        }
+        // mov reg reg = move, mov reg 0 = sub reg reg, mov reg number = imovel, mov reg name = imovel with the address
+        ("mov", [reg, value]) => {
+            let reg = register(reg)?;
+            if let Ok(number) = parse_word(value) {
+                if number == 0 {
+                    vec![Asm(Sub(reg, reg))]
+                } else {
+                    vec![Asm(IMoveL(reg, number))]
+                }
+            } else if let Some(source) = get_id_from_reg_name(value) {
+                vec![Asm(MoveR(reg, source))]
+            } else if is_symbol_name(value) {
+                vec![PreAsm(IMoveL(reg, 0), scope.reference(value, location)?)]
+            } else {
+                return Err(error(format!("mov expects a register, number, label or variable, found {value}")));
+            }
+        }
+        // neg reg = move tr reg, sub reg reg, sub reg tr
+        ("neg", [reg]) => {
+            let reg = register(reg)?;
+            vec![Asm(MoveR(TR, reg)), Asm(Sub(reg, reg)), Asm(Sub(reg, TR))]
+        }
+        // nop = jmp to the next address, which leaves the flags and every register but tr alone
+        ("nop", []) => {
+            let next = scope.internal();
+            vec![PreAsm(JMP(0), next.clone()), Label(next)]
+        }
+        ("mov", _) => return Err(error("mov expects a register and a value, e.g. mov acc 5".to_string())),
+        ("nop", _) => return Err(error("nop does not take any arguments".to_string())),
+        _ => return Err(error(format!("{name} expects a register, e.g. {name} acc"))),
+    };
+    Ok(expansion)
+}
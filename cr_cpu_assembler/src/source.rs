@@ -0,0 +1,225 @@
+use crate::conditional::{parse_constant, Conditionals};
+use crate::error::AssemblerError;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The file and line number a piece of source code came from, used to report errors
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    /// Line number in the file, starting at 1
+    pub line: usize,
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+/// A single line of source code, remembering where it came from
+#[derive(Debug, Clone)]
+pub struct SourceLine {
+    pub location: SourceLocation,
+    pub text: String,
+}
+
+impl SourceLine {
+    /// Split the line into its tokens, see `tokenize`
+    pub fn tokens(&self) -> Result<Vec<String>, AssemblerError> {
+        tokenize(&self.text).map_err(|msg| AssemblerError::new(&self.location, msg))
+    }
+}
+
+/// Read a source file into a list of lines, replacing every `.include "path"` line with the lines of the file it names.
+/// Include paths are relative to the file that contains the include.
+/// A file is only ever included once, so a library can be included by several files without its labels being defined twice,
+/// including a file that is currently being read is reported as an include cycle.
+/// Conditional assembly is done while reading, `constants` are the constants defined before the first line,
+/// so lines in blocks whose condition is not met are left out, and an `.include` in such a block is never read.
+/// Conditional directives and `.equ` lines are left out as well, as they have been dealt with
+pub fn load_source(path: &Path, constants: HashMap<String, u32>) -> Result<Vec<SourceLine>, AssemblerError> {
+    let mut loader = SourceLoader {
+        constants,
+        ..Default::default()
+    };
+    loader.load(path, None)?;
+    loader.conditionals.finish()?;
+    Ok(loader.lines)
+}
+
+#[derive(Default)]
+struct SourceLoader {
+    lines: Vec<SourceLine>,
+    /// Every file that has been included so far, used as an include guard
+    included: HashSet<PathBuf>,
+    /// The chain of files currently being read, used to detect include cycles
+    stack: Vec<PathBuf>,
+    /// Conditional assembly blocks, which can start in one file and end in another
+    conditionals: Conditionals,
+    /// Constants for `.if` and `.ifdef`, from the command line, the profile and `.equ` lines read so far
+    constants: HashMap<String, u32>,
+}
+
+impl SourceLoader {
+    fn load(&mut self, path: &Path, included_from: Option<&SourceLocation>) -> Result<(), AssemblerError> {
+        let read_error = |msg: String| match included_from {
+            Some(location) => AssemblerError::new(location, msg),
+            None => AssemblerError::without_location(msg),
+        };
+
+        let canonical = fs::canonicalize(path)
+            .map_err(|err| read_error(format!("unable to read '{}': {err}", path.display())))?;
+
+        if self.stack.contains(&canonical) {
+            let chain = self
+                .stack
+                .iter()
+                .chain([&canonical])
+                .map(|file| file.display().to_string())
+                .collect::<Vec<String>>()
+                .join(" -> ");
+            return Err(read_error(format!("include cycle: {chain}")));
+        }
+        if !self.included.insert(canonical.clone()) {
+            // this file has already been included, so its contents are already in the program
+            return Ok(());
+        }
+
+        let text = fs::read_to_string(path)
+            .map_err(|err| read_error(format!("unable to read '{}': {err}", path.display())))?;
+
+        self.stack.push(canonical);
+        for (index, text) in text.lines().enumerate() {
+            let line = SourceLine {
+                location: SourceLocation {
+                    file: path.to_path_buf(),
+                    line: index + 1,
+                },
+                text: text.to_string(),
+            };
+
+            let tokens = line.tokens()?;
+            if tokens.is_empty() {
+                continue;
+            }
+            // lines inside conditional blocks whose condition is not met are skipped
+            if self.conditionals.handle(&tokens, &line.location, &self.constants)? || !self.conditionals.is_active() {
+                continue;
+            }
+            // .equ NAME expression defines a constant for conditional assembly
+            if tokens[0].eq_ignore_ascii_case(".equ") {
+                let (name, value) =
+                    parse_constant(&tokens, &self.constants).map_err(|msg| AssemblerError::new(&line.location, msg))?;
+                self.constants.insert(name, value);
+                continue;
+            }
+            if tokens.first().map(|token| token.to_lowercase()).as_deref() == Some(".include") {
+                let include_path = match tokens.as_slice() {
+                    [_, quoted] => unquote(quoted).ok_or_else(|| {
+                        AssemblerError::new(&line.location, ".include expects a quoted path, e.g. .include \"lib.cr\"")
+                    })?,
+                    _ => {
+                        return Err(AssemblerError::new(
+                            &line.location,
+                            ".include expects a single quoted path, e.g. .include \"lib.cr\"",
+                        ));
+                    }
+                };
+                let resolved = path
+                    .parent()
+                    .unwrap_or_else(|| Path::new(""))
+                    .join(include_path);
+                self.load(&resolved, Some(&line.location))?;
+            } else {
+                self.lines.push(line);
+            }
+        }
+        self.stack.pop();
+
+        Ok(())
+    }
+}
+
+/// Split a line of source code into tokens.
+/// Tokens are separated by whitespace or commas, everything after a `;` is a comment,
+/// text in double quotes is kept together as one token including its quotes,
+/// and a memory operand in square brackets such as `[sp - 1]` is kept together as one token including its brackets.
+pub fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => break,
+            '"' => {
+                current.push(c);
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    current.push(c);
+                    if c == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            current.push(escaped);
+                        }
+                    } else if c == '"' {
+                        closed = true;
+                        break;
+                    }
+                }
+                if !closed {
+                    return Err(format!("unterminated string: {current}"));
+                }
+            }
+            '[' => {
+                current.push(c);
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    current.push(c);
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                }
+                if !closed {
+                    return Err(format!("unterminated memory operand: {current}"));
+                }
+            }
+            c if c.is_whitespace() || c == ',' => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+/// Remove the quotes from a quoted token and process its escape sequences,
+/// returns None if the token is not a quoted string
+pub fn unquote(token: &str) -> Option<String> {
+    let inner = token.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                'r' => out.push('\r'),
+                '0' => out.push('\0'),
+                other => out.push(other),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Some(out)
+}
@@ -4,13 +4,39 @@
 pub const MOVER: u8 = 0x01;
 pub const IMOVEL: u8 = 0x11;
 pub const MOVEA: u8 = 0x13;
+/// Move register into the dram address held in another register
+pub const MOVEAR: u8 = 0x24;
+/// Move register into the dram address of a base register plus an offset
+pub const MOVEAO: u8 = 0x25;
 
 /// Load effective address
 pub const LEA: u8 = 0x12;
+/// Load a byte or half word into a register, zero extending it, or sign extending it for the `S` opcodes
+pub const LDB: u8 = 0x50;
+pub const LDBS: u8 = 0x51;
+pub const LDH: u8 = 0x52;
+pub const LDHS: u8 = 0x53;
+/// Store the low byte or half word of a register
+pub const STB: u8 = 0x54;
+pub const STH: u8 = 0x55;
 /// Load effective address register
 pub const LEAR: u8 = 0x14;
+/// Load effective address of a base register plus an offset
+pub const LEAO: u8 = 0x26;
 pub const SHL: u8 = 0x15;
 pub const SHR: u8 = 0x16;
+/// Arithmetic shift right, copying the sign bit into the bits shifted in
+pub const SAR: u8 = 0x30;
+/// Rotate left
+pub const ROL: u8 = 0x31;
+/// Rotate right
+pub const ROR: u8 = 0x32;
+/// Shift and rotate opcodes that take the amount from a register
+pub const SHLR: u8 = 0x33;
+pub const SHRR: u8 = 0x34;
+pub const SARR: u8 = 0x35;
+pub const ROLR: u8 = 0x36;
+pub const RORR: u8 = 0x37;
 
 /// Compare register opcode
 pub const CMP: u8 = 0x02;
@@ -18,6 +44,10 @@ pub const CMP: u8 = 0x02;
 pub const ICMP: u8 = 0xA2;
 /// Immediate mode compare long number opcode
 pub const ICMPL: u8 = 0xB2;
+/// Signed compare register opcode
+pub const CMPS: u8 = 0xC2;
+/// Immediate mode signed compare opcode
+pub const ICMPS: u8 = 0xD2;
 
 // Jump instruction opcodes
 /// Jump equal to
@@ -32,19 +62,82 @@ pub const JZ: u8 = 0x06;
 pub const JOV: u8 = 0x07;
 /// Jump
 pub const JMP: u8 = 0x08;
+/// Jump greater than, signed
+pub const JGTS: u8 = 0x09;
+/// Jump less than, signed
+pub const JLTS: u8 = 0x0D;
+/// Jump greater than or equal to, signed
+pub const JGES: u8 = 0x0E;
+/// Jump less than or equal to, signed
+pub const JLES: u8 = 0x0F;
+/// Jump not equal to
+pub const JNE: u8 = 0x17;
+/// Jump not zero
+pub const JNZ: u8 = 0x18;
+/// Jump greater than or equal to
+pub const JGE: u8 = 0x19;
+/// Jump less than or equal to
+pub const JLE: u8 = 0x1D;
+/// Jump not overflow
+pub const JNO: u8 = 0x1E;
+/// Jump carry
+pub const JC: u8 = 0x1F;
+/// Jump not carry
+pub const JNC: u8 = 0x27;
+/// Jump to the address in a register
+pub const JMPR: u8 = 0x23;
+/// Loop opcode, decrements cr then jumps if cr is not zero
+pub const LOOP: u8 = 0x28;
+
+/// Call opcode, pushes the return address then jumps
+pub const CALL: u8 = 0x20;
+/// Call the address in a register
+pub const CALLR: u8 = 0x21;
+/// Return opcode, pops the return address into pc
+pub const RET: u8 = 0x22;
 
 /// Add instruction opcodes
 /// | unused | | number to add | | location to add to (unused at the moment) | | op-code |
 pub const IADD: u8 = 0x0A;
+/// Increment a register by one
+pub const INC: u8 = 0x29;
+/// Decrement a register by one
+pub const DEC: u8 = 0x2D;
 /// Add instruction opcode for adding one register into another
 pub const ADD: u8 = 0x2A;
 /// Immediate mode add long number opcode
 pub const IADDL: u8 = 0x1A;
+/// Add with carry opcode for adding one register and the carry flag into another
+pub const ADC: u8 = 0x3A;
+/// Immediate mode add with carry opcode
+pub const IADC: u8 = 0x4A;
 
 /// Subtract instruction
 pub const ISUB: u8 = 0x0B;
 /// Subtract opcode
 pub const SUB: u8 = 0x1B;
+/// Subtract with borrow opcode, subtracting one register and the carry flag from another
+pub const SBB: u8 = 0x2B;
+/// Immediate mode subtract with borrow opcode
+pub const ISBB: u8 = 0x3B;
+
+/// Bit test opcodes, copying a bit into carry then leaving it, setting, resetting or complementing it,
+/// with the bit index in a register
+pub const BT: u8 = 0x40;
+pub const BTS: u8 = 0x41;
+pub const BTR: u8 = 0x42;
+pub const BTC: u8 = 0x43;
+/// Immediate mode bit test opcodes, with the bit index in the instruction
+pub const IBT: u8 = 0x44;
+pub const IBTS: u8 = 0x45;
+pub const IBTR: u8 = 0x46;
+pub const IBTC: u8 = 0x47;
+/// Count the set bits of a register
+pub const POPCNT: u8 = 0x48;
+/// Count the leading zero bits of a register
+pub const CLZ: u8 = 0x49;
+/// Count the trailing zero bits of a register
+pub const CTZ: u8 = 0x4B;
 
 /// Push instruction opcode
 pub const IPUSH: u8 = 0x0C;
@@ -71,7 +164,14 @@ pub const PC: u8 = 0x1A;
 pub const IR: u8 = 0x2A;
 pub const OR: u8 = 0x3A;
 pub const SP: u8 = 0x4A;
+/// Temporary register, the decoder loads long immediates and jump targets into it,
+/// so it has no name in assembly and is only used by the assembler in pseudo-instruction expansions
 pub const TR: u8 = 0x5A;
+/// General purpose registers R0 to R15 have the ids R0..=R15
+pub const R0: u8 = 0x80;
+pub const R15: u8 = 0x8F;
+/// Number of general purpose registers
+pub const GENERAL_REGISTERS: usize = 16;
 
 /// Using a name, get the id of a register if there is one
 /// Used in the compiler to determine what the user intends when they specify a register
@@ -82,9 +182,15 @@ pub fn get_id_from_reg_name(name: &str) -> Option<u8> {
         "IR" => Some(IR),
         "OR" => Some(OR),
         "SP" => Some(SP),
-        "TR" => Some(TR),
         "CR" => Some(CR),
-        _ => None,
+        name => {
+            let index: u8 = name.strip_prefix('R')?.parse().ok()?;
+            // reject names like R01, so every register has exactly one name
+            if index as usize >= GENERAL_REGISTERS || name != format!("R{index}") {
+                return None;
+            }
+            Some(R0 + index)
+        }
     }
 }
 
@@ -99,6 +205,7 @@ pub fn get_name_from_reg_id(id: u8) -> Option<String> {
         SP => Some("SP".to_string()),
         TR => Some("TR".to_string()),
         CR => Some("CR".to_string()),
+        R0..=R15 => Some(format!("R{}", id - R0)),
         _ => {
             if id != 0 {
                 dbg!(id);
@@ -114,3 +221,5 @@ pub const GREATER_FLAG: u8 = 0x2F;
 pub const LESS_FLAG: u8 = 0x3F;
 pub const EQUAL_FLAG: u8 = 0x4F;
 pub const OV: u8 = 0x5F;
+pub const SIGN_FLAG: u8 = 0x6F;
+pub const CARRY_FLAG: u8 = 0x7F;
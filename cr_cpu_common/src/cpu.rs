@@ -1,8 +1,13 @@
 use crate::constants::*;
+use crate::debug_info::DebugInfo;
+use crate::fault::Fault;
 use crate::instruction::Instruction;
 use crate::instruction::Instruction::{
-    Add, Cmp, Dump, DumpR, IAdd, IAddL, ICmp, ICmpL, IMoveL, IPushL, ISub, Lea, LeaR, MoveA, MoveR,
-    Push, Shl, Shr, Sub, Unknown, JE, JGT, JLT, JMP, JOV, JZ,
+    Adc, Add, Bt, Btc, Btr, Bts, Call, CallR, Clz, Cmp, Cmps, Ctz, Dec, Dump, DumpR, IAdc, IAdd,
+    IAddL, IBt, IBtc, IBtr, IBts, ICmp, ICmpL, ICmps, IMoveL, IPushL, ISbb, ISub, Inc, JmpR, Ldb,
+    Ldbs, Ldh, Ldhs, Lea, LeaO, LeaR, Loop, MoveA, MoveAO, MoveAR, MoveR, Popcnt, Push, Ret, Rol,
+    RolR, Ror, RorR, Sar, SarR, Sbb, Shl, ShlR, Shr, ShrR, Stb, Sth, Sub, Unknown, JC, JE, JGE,
+    JGES, JGT, JGTS, JLE, JLES, JLT, JLTS, JMP, JNC, JNE, JNO, JNZ, JOV, JZ,
 };
 use crate::mask_bit_group;
 use crate::prelude::{IPush, Pop};
@@ -12,6 +17,31 @@ use std::io;
 use std::io::Read;
 use std::path::PathBuf;
 
+/// Tests the flags of the cpu, returning true if a jump should be taken
+type JumpCondition = fn(&Cpu) -> bool;
+
+/// The condition each jump opcode tests, a jump moves pc to its target when its condition is true.
+/// Adding a jump on a new flag only needs an entry here, as every jump decodes and executes the same way
+const JUMP_CONDITIONS: [(u8, JumpCondition); 17] = [
+    (crate::constants::JMP, |_| true),
+    (crate::constants::JE, |cpu| cpu.eq_flag),
+    (crate::constants::JNE, |cpu| !cpu.eq_flag),
+    (crate::constants::JGT, |cpu| cpu.gt_flag),
+    (crate::constants::JGE, |cpu| cpu.gt_flag || cpu.eq_flag),
+    (crate::constants::JLT, |cpu| cpu.lt_flag),
+    (crate::constants::JLE, |cpu| cpu.lt_flag || cpu.eq_flag),
+    (crate::constants::JZ, |cpu| cpu.zero_flag),
+    (crate::constants::JNZ, |cpu| !cpu.zero_flag),
+    (crate::constants::JOV, |cpu| cpu.ov_flag),
+    (crate::constants::JNO, |cpu| !cpu.ov_flag),
+    (crate::constants::JC, |cpu| cpu.carry_flag),
+    (crate::constants::JNC, |cpu| !cpu.carry_flag),
+    (crate::constants::JGTS, |cpu| !cpu.zero_flag && cpu.sign_flag == cpu.ov_flag),
+    (crate::constants::JLTS, |cpu| cpu.sign_flag != cpu.ov_flag),
+    (crate::constants::JGES, |cpu| cpu.sign_flag == cpu.ov_flag),
+    (crate::constants::JLES, |cpu| cpu.zero_flag || cpu.sign_flag != cpu.ov_flag),
+];
+
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct Cpu {
@@ -38,18 +68,36 @@ pub struct Cpu {
     /// Stack pointer
     /// Index where the stack currently is at in dram
     sp: u32,
+    /// Index of the first word of the stack, below it are the variables, popping past it is a fault
+    stack_base: u32,
 
-    /// Temporary register
+    /// Temporary register, internal to the cpu and assembler
     tr: u32,
 
+    /// General purpose registers R0 to R15
+    r: [u32; GENERAL_REGISTERS],
+
     /// Ram, also used as stack memory
     dram: [u32; DRAM_SIZE as usize],
+    /// Which words of dram hold part of the program, tracked separately from their values so that zero words can be used
+    occupied: [bool; DRAM_SIZE as usize],
+    /// Source lines, labels and variables of the program, only present in binaries built with the debug profile
+    debug_info: DebugInfo,
+    /// Print every instruction as it is executed, along with the registers it uses
+    trace: bool,
+    /// The fault that stopped the program, if there was one
+    fault: Option<Fault>,
 
     zero_flag: bool,
     lt_flag: bool,
     gt_flag: bool,
     eq_flag: bool,
+    /// Set when the result of a signed addition or subtraction does not fit in 32 bits
     ov_flag: bool,
+    /// Set when the result of an addition or subtraction is negative as a signed number
+    sign_flag: bool,
+    /// Set when an unsigned addition carries out of 32 bits, or an unsigned subtraction borrows
+    carry_flag: bool,
     // TODO: stack memory ? heap memory?
     // TODO: flags?
 }
@@ -72,13 +120,21 @@ impl Cpu {
             // inpr1: EMPTY_INPUT_REGISTER,
             // inpr2: EMPTY_INPUT_REGISTER,
             sp: DRAM_SIZE - (DRAM_SIZE / 4),
+            stack_base: DRAM_SIZE - (DRAM_SIZE / 4),
             tr: EMPTY_REGISTER,
+            r: [EMPTY_REGISTER; GENERAL_REGISTERS],
             dram: EMPTY_DRAM,
+            occupied: [false; DRAM_SIZE as usize],
+            debug_info: DebugInfo::default(),
+            trace: false,
+            fault: None,
             zero_flag: false,
             lt_flag: false,
             gt_flag: false,
             eq_flag: false,
             ov_flag: false,
+            sign_flag: false,
+            carry_flag: false,
         }
     }
 
@@ -86,10 +142,12 @@ impl Cpu {
         &self.dram
     }
 
+    /// Store a variable at the start of the stack area, moving the stack pointer and the base of the stack past it
     pub fn push_variable(&mut self, value: u32) -> u32 {
-        *self.dram.get_mut(self.sp as usize).unwrap() = value;
+        self.add_instruction(value, self.sp);
         let r = self.sp;
         self.sp += 1;
+        self.stack_base = self.sp;
         r
     }
 
@@ -103,12 +161,16 @@ impl Cpu {
     }
 
     /// Interpret a binary and create a cpu from it, this binary is not checked for validity
+    /// A binary does not record which words are used, so only non zero words are marked as occupied
+    /// Debug info is read from the `.dbg` file next to the binary, if there is one
+    /// The base of the stack is taken from the `imovel sp` the assembler places at address 0 for programs with variables
     pub fn from_binary(path: PathBuf) -> Result<Self, io::Error> {
         let mut cpu = Self::new();
         let mut file = File::open(&path)?;
         let mut buf = vec![];
         file.read_to_end(&mut buf).unwrap();
-        let mut iter = buf.iter();
+        let dram = &buf[..buf.len().min(DRAM_SIZE as usize * 4)];
+        let mut iter = dram.iter();
         let mut i = 0;
         #[allow(clippy::while_let_loop)]
         loop {
@@ -121,7 +183,8 @@ impl Cpu {
                                 | (*g2 as u32) << 16
                                 | (*g3 as u32) << 24;
 
-                            cpu.add_instruction(inst, i);
+                            cpu.dram[i as usize] = inst;
+                            cpu.occupied[i as usize] = inst != 0;
 
                             i += 1;
                         } else {
@@ -138,60 +201,103 @@ impl Cpu {
             }
         }
 
+        // programs with variables start with `imovel sp` past them, which is also where the stack begins
+        if mask_bit_group(cpu.dram[0], 0) == IMOVEL && mask_bit_group(cpu.dram[0], 1) == SP {
+            cpu.stack_base = cpu.dram[1];
+        }
+
+        let debug_info_path = path.with_extension("dbg");
+        if debug_info_path.exists() {
+            cpu.debug_info = DebugInfo::read(&debug_info_path)?;
+        }
+
         Ok(cpu)
     }
 
-    /// Force an instruction into a given location, overwriting what ever is there
+    /// Convert dram into a binary that can be read with `from_binary`, each word is stored in little endian order
+    /// Debug info is not part of the binary, it is written to its own file with `DebugInfo::write`
+    pub fn to_binary(&self) -> Vec<u8> {
+        self.dram.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    /// Give the cpu the debug info of the program in dram, so execution and dumps can show source code
+    pub fn set_debug_info(&mut self, debug_info: DebugInfo) {
+        self.debug_info = debug_info;
+    }
+
+    pub fn get_debug_info(&self) -> &DebugInfo {
+        &self.debug_info
+    }
+
+    /// The fault that stopped the program, if it was stopped by one
+    pub fn get_fault(&self) -> Option<&Fault> {
+        self.fault.as_ref()
+    }
+
+    /// Turn the execution trace on or off, it is off by default
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Force an instruction into a given location, overwriting what ever is there, and mark the location as occupied
     fn add_instruction(&mut self, inst: u32, location: u32) {
         *self.dram.get_mut(location as usize).unwrap() = inst;
+        self.occupied[location as usize] = true;
+    }
+
+    /// Write a list of words into dram starting at a given location, overwriting what ever is there
+    pub fn write_dram(&mut self, location: u32, data: &[u32]) {
+        for (offset, word) in data.iter().enumerate() {
+            self.add_instruction(*word, location + offset as u32);
+        }
     }
 
     /// Add an instruction to the first available space in dram,
     /// checking for if the instruction size can fit
+    /// Space is available if no instruction has been placed there, whatever its value, so instructions holding zeros are never overwritten
     pub fn add_to_end(&mut self, inst: &Instruction) {
-        for (index, inst_dram) in self.dram.clone().iter().enumerate() {
-            if *inst_dram == 0x0 {
-                // if the instruction read is 0x0 allow the program to put that instruction into this memory address
-                let inst_list = inst.to_instruction_data();
-                // boolean value which checks if the input instruction fits into the space of memory found, if it does not, keep searching
-                let instruction_fits = !self
-                    .dram
-                    .iter()
-                    .enumerate()
-                    .skip(index) // skip to the index we are currently at in dram, so we dont check any areas which are not needed
-                    .take_while(|(check_index, _)| check_index < &(index + inst_list.len())) // end the iterator at the location that is where our index is + the instruction length
-                    .any(|(_, item)| *item != 0x00); // return true if any of those items are not 0x00
-
-                if instruction_fits {
-                    // if the instruction fits, place that instruction in memory, and all of its components
-                    for (add_index, ins) in inst_list.iter().enumerate() {
-                        self.add_instruction(*ins, (index + add_index) as u32);
-                    }
-                    break;
-                }
-            }
+        let inst_list = inst.to_instruction_data();
+        // find the first run of unoccupied words that is long enough to hold every word of the instruction
+        let free_index = self
+            .occupied
+            .windows(inst_list.len())
+            .position(|window| window.iter().all(|occupied| !occupied));
+
+        if let Some(index) = free_index {
+            self.write_dram(index as u32, &inst_list);
         }
     }
 
     /// Fetch the instruction from `dram` and increment the `program counter`
     /// Fetch decodes the instruction as well
     fn fetch(&mut self) -> Instruction {
-        self.ir = *self.dram.get(self.pc as usize).unwrap();
-        self.pc += 1;
+        self.fetch_value_ir();
         self.decode()
     }
 
+    /// Fetch and decode the instruction at an address without executing it
+    pub(crate) fn fetch_at(&mut self, address: u32) -> Instruction {
+        self.pc = address;
+        self.fetch()
+    }
+
     /// Fetches the next address in dram as a u32, useful for instructions that span multiple memory address locations
     /// stores output in temporary register
     fn fetch_value_tr(&mut self) {
-        self.tr = *self.dram.get(self.pc as usize).unwrap();
-        self.pc += 1;
+        self.tr = self.fetch_word();
     }
 
     /// Fetches the next address in dram as u32 without decoding, storing it in the instruction register
     fn fetch_value_ir(&mut self) {
-        self.ir = *self.dram.get(self.pc as usize).unwrap();
-        self.pc += 1;
+        self.ir = self.fetch_word();
+    }
+
+    /// Fetches the word at pc and increments pc, words past the end of dram are read as 0,
+    /// `execute` faults before running an instruction that was fetched from past the end
+    fn fetch_word(&mut self) -> u32 {
+        let word = self.dram.get(self.pc as usize).copied().unwrap_or(0);
+        self.pc = self.pc.wrapping_add(1);
+        word
     }
 
     /// Decode a single opcode into an instruction,
@@ -209,13 +315,37 @@ impl Cpu {
             MOVER => MoveR(0, 0),
             IMOVEL => IMoveL(0, 0),
             CMP => Cmp(0, 0),
+            CMPS => Cmps(0, 0),
+            ICMPS => ICmps(0, 0),
             crate::constants::JE => JE(0),
             crate::constants::JGT => JGT(0),
             crate::constants::JLT => JLT(0),
             crate::constants::JZ => JZ(0),
             crate::constants::JOV => JOV(0),
             crate::constants::JMP => JMP(0),
+            crate::constants::JGTS => JGTS(0),
+            crate::constants::JLTS => JLTS(0),
+            crate::constants::JGES => JGES(0),
+            crate::constants::JLES => JLES(0),
+            crate::constants::JNE => JNE(0),
+            crate::constants::JNZ => JNZ(0),
+            crate::constants::JGE => JGE(0),
+            crate::constants::JLE => JLE(0),
+            crate::constants::JNO => JNO(0),
+            crate::constants::JC => JC(0),
+            crate::constants::JNC => JNC(0),
+            JMPR => JmpR(0),
+            LOOP => Loop(0),
+            INC => Inc(0),
+            DEC => Dec(0),
+            CALL => Call(0),
+            CALLR => CallR(0),
+            RET => Ret,
             SUB => Sub(0, 0),
+            ADC => Adc(0, 0),
+            IADC => IAdc(0),
+            SBB => Sbb(0, 0),
+            ISBB => ISbb(0),
             ICMP => ICmp(0, 0),
             ICMPL => ICmpL(0, 0),
             IPUSHL => IPushL(0),
@@ -224,8 +354,36 @@ impl Cpu {
             LEA => Lea(0),
             MOVEA => MoveA(0, 0),
             LEAR => LeaR(0),
+            LEAO => LeaO(0, 0),
+            LDB => Ldb(0, 0, 0),
+            LDBS => Ldbs(0, 0, 0),
+            LDH => Ldh(0, 0, 0),
+            LDHS => Ldhs(0, 0, 0),
+            STB => Stb(0, 0, 0),
+            STH => Sth(0, 0, 0),
+            MOVEAR => MoveAR(0, 0),
+            MOVEAO => MoveAO(0, 0, 0),
             SHL => Shl(0, 0),
             SHR => Shr(0, 0),
+            SAR => Sar(0, 0),
+            ROL => Rol(0, 0),
+            ROR => Ror(0, 0),
+            SHLR => ShlR(0, 0),
+            SHRR => ShrR(0, 0),
+            SARR => SarR(0, 0),
+            ROLR => RolR(0, 0),
+            RORR => RorR(0, 0),
+            BT => Bt(0, 0),
+            BTS => Bts(0, 0),
+            BTR => Btr(0, 0),
+            BTC => Btc(0, 0),
+            IBT => IBt(0, 0),
+            IBTS => IBts(0, 0),
+            IBTR => IBtr(0, 0),
+            IBTC => IBtc(0, 0),
+            POPCNT => Popcnt(0, 0),
+            CLZ => Clz(0, 0),
+            CTZ => Ctz(0, 0),
             _ => Unknown,
         }
     }
@@ -243,41 +401,33 @@ impl Cpu {
         let group3 = mask_bit_group(self.ir, 3);
 
         match Cpu::decode_inst(op_code) {
+            // every jump decodes its target into tr, which execute moves into pc if the jump is taken
+            mut jump @ (JE(_) | JGT(_) | JLT(_) | JZ(_) | JOV(_) | JMP(_) | JGTS(_) | JLTS(_) | JGES(_)
+            | JLES(_) | JNE(_) | JNZ(_) | JGE(_) | JLE(_) | JNO(_) | JC(_) | JNC(_) | Loop(_)) => {
+                self.tr = ((group1 as u16) | ((group2 as u16) << 8)) as u32;
+                jump.set_address_operand(self.tr);
+                jump
+            }
+            JmpR(_) => JmpR(group1),
+            Call(_) => {
+                self.tr = ((group1 as u16) | ((group2 as u16) << 8)) as u32;
+                Call(self.tr as u16)
+            }
+            CallR(_) => CallR(group1),
+            Ret => Ret,
             MoveR(_, _) => MoveR(group1, group2),
             IMoveL(_, _) => {
                 self.fetch_value_tr();
                 IMoveL(group1, self.tr)
             }
             Cmp(_, _) => Cmp(group1, group2),
-            JE(_) => {
-                self.tr = ((group1 as u16) | ((group2 as u16) << 8)) as u32;
-                JE(self.tr as u16)
-            }
-            JGT(_) => {
-                self.tr = ((group1 as u16) | ((group2 as u16) << 8)) as u32;
-                JGT(self.tr as u16)
-            }
-            JLT(_) => {
-                self.tr = ((group1 as u16) | ((group2 as u16) << 8)) as u32;
-                JLT(self.tr as u16)
-            }
-            JZ(_) => {
-                self.tr = ((group1 as u16) | ((group2 as u16) << 8)) as u32;
-                JZ(self.tr as u16)
-            }
-            JOV(_) => {
-                self.tr = ((group1 as u16) | ((group2 as u16) << 8)) as u32;
-                JOV(self.tr as u16)
-            }
-            JMP(_) => {
-                self.tr = ((group1 as u16) | ((group2 as u16) << 8)) as u32;
-                JMP(self.tr as u16)
-            }
             IAdd(_) => {
                 self.tr = group2 as u32;
                 IAdd(group2)
             }
             Add(_, _) => Add(group1, group2),
+            Inc(_) => Inc(group1),
+            Dec(_) => Dec(group1),
             IAddL(_) => {
                 self.fetch_value_tr();
                 IAddL(self.tr)
@@ -287,6 +437,16 @@ impl Cpu {
                 ISub(group2)
             }
             Sub(_, _) => Sub(group1, group2),
+            IAdc(_) => {
+                self.tr = group2 as u32;
+                IAdc(group2)
+            }
+            Adc(_, _) => Adc(group1, group2),
+            ISbb(_) => {
+                self.tr = group2 as u32;
+                ISbb(group2)
+            }
+            Sbb(_, _) => Sbb(group1, group2),
             IPush(_) => {
                 // self.tr = ((group1 as u16) | ((group2 as u16) << 8)) as u32;
                 IPush((group1 as u16) | ((group2 as u16) << 8))
@@ -299,6 +459,8 @@ impl Cpu {
                 self.fetch_value_tr();
                 ICmpL(group1, self.tr)
             }
+            Cmps(_, _) => Cmps(group1, group2),
+            ICmps(_, _) => ICmps(group1, ((group2 as u16) | ((group3 as u16) << 8)) as i16),
             IPushL(_) => {
                 self.fetch_value_tr();
                 IPushL(self.tr)
@@ -308,13 +470,44 @@ impl Cpu {
             Lea(_) => Lea((group1 as u16) | ((group2 as u16) << 8)),
             MoveA(_, _) => MoveA((group1 as u16) | ((group2 as u16) << 8), group3),
             LeaR(_) => LeaR(group1),
+            LeaO(_, _) => LeaO(group1, group2 as i8),
+            Ldb(_, _, _) => Ldb(group1, group2, group3 as i8),
+            Ldbs(_, _, _) => Ldbs(group1, group2, group3 as i8),
+            Ldh(_, _, _) => Ldh(group1, group2, group3 as i8),
+            Ldhs(_, _, _) => Ldhs(group1, group2, group3 as i8),
+            Stb(_, _, _) => Stb(group1, group2 as i8, group3),
+            Sth(_, _, _) => Sth(group1, group2 as i8, group3),
+            MoveAR(_, _) => MoveAR(group1, group2),
+            MoveAO(_, _, _) => MoveAO(group1, group2 as i8, group3),
             Shl(_, _) => Shl(group1, group2),
             Shr(_, _) => Shr(group1, group2),
+            Sar(_, _) => Sar(group1, group2),
+            Rol(_, _) => Rol(group1, group2),
+            Ror(_, _) => Ror(group1, group2),
+            ShlR(_, _) => ShlR(group1, group2),
+            ShrR(_, _) => ShrR(group1, group2),
+            SarR(_, _) => SarR(group1, group2),
+            RolR(_, _) => RolR(group1, group2),
+            RorR(_, _) => RorR(group1, group2),
+            Bt(_, _) => Bt(group1, group2),
+            Bts(_, _) => Bts(group1, group2),
+            Btr(_, _) => Btr(group1, group2),
+            Btc(_, _) => Btc(group1, group2),
+            IBt(_, _) => IBt(group1, group2),
+            IBts(_, _) => IBts(group1, group2),
+            IBtr(_, _) => IBtr(group1, group2),
+            IBtc(_, _) => IBtc(group1, group2),
+            Popcnt(_, _) => Popcnt(group1, group2),
+            Clz(_, _) => Clz(group1, group2),
+            Ctz(_, _) => Ctz(group1, group2),
         }
     }
 
     /// Print the bitmask group 1 and 2 of IR, typically inpr1 and inpr2
     fn print_inpr_regs(&self) -> Option<()> {
+        if !self.trace {
+            return Some(());
+        }
         let inpr1 = mask_bit_group(self.ir, 1);
         let inpr2 = mask_bit_group(self.ir, 2);
         let reg0 = get_name_from_reg_id(inpr1)?;
@@ -325,6 +518,9 @@ impl Cpu {
 
     /// Print bit mask ground 1 from IR, typically the first inpr
     fn print_inpr_reg(&self) -> Option<()> {
+        if !self.trace {
+            return Some(());
+        }
         let inpr1 = mask_bit_group(self.ir, 1);
         let reg0 = get_name_from_reg_id(inpr1)?;
         println!("{inpr1}: {reg0}");
@@ -332,6 +528,9 @@ impl Cpu {
     }
 
     fn print_inpr_reg_specific(&self, group: u8) -> Option<()> {
+        if !self.trace {
+            return Some(());
+        }
         let inpr1 = mask_bit_group(self.ir, group);
         let reg0 = get_name_from_reg_id(inpr1)?;
         println!("{inpr1}: {reg0}");
@@ -340,33 +539,43 @@ impl Cpu {
 
     /// Execute the instruction in the instruction register
     fn execute(&mut self, inst: Instruction) {
-        #[cfg(debug_assertions)]
-        {
-            let len = inst.to_instruction_data().len() as u32;
-            println!("Instruction executed: [{}]: {:?}", self.pc - len, inst);
+        let address = self.pc - inst.to_instruction_data().len() as u32;
+        if self.trace {
+            println!("Instruction executed: [{address}]: {inst:?}");
+            if let Some(annotation) = self.debug_info.annotate(address) {
+                println!("    {annotation}");
+            }
+        }
+        // the instruction ran off the end of dram part way through, so its operands are missing
+        if self.pc > DRAM_SIZE {
+            self.fault = Some(Fault::JumpOutOfMemory { address, target: DRAM_SIZE });
+            return;
         }
+        let is_unknown = matches!(inst, Unknown);
 
         match inst {
             // we dont use any values passed from the instruction itself to better make use of the cpu registers
             IAdd(_) | IAddL(_) => {
-                let (outcome, chk) = self.acc.overflowing_add(self.tr);
-                self.acc = outcome;
-                self.ov_flag = chk;
-                self.zero_flag = self.acc == 0;
+                self.acc = self.add_with_flags(self.acc, self.tr, false);
             }
             ISub(_) => {
-                let (outcome, chk) = self.acc.overflowing_sub(self.tr);
-                self.acc = outcome;
-                self.ov_flag = chk;
-                self.zero_flag = self.acc == 0;
+                self.acc = self.sub_with_flags(self.acc, self.tr, false);
+            }
+            IAdc(_) => {
+                self.acc = self.add_with_flags(self.acc, self.tr, self.carry_flag);
+            }
+            ISbb(_) => {
+                self.acc = self.sub_with_flags(self.acc, self.tr, self.carry_flag);
             }
             Unknown => {
                 let inst = self.fetch();
                 if matches!(inst, Instruction::Unknown) {
-                    println!(
-                        "Reached end of program by finding two unknown instructions, pc: {}",
-                        self.pc
-                    );
+                    if self.trace {
+                        println!(
+                            "Reached end of program by finding two unknown instructions, pc: {}",
+                            self.pc
+                        );
+                    }
                 } else {
                     println!("Unknown instruction");
                     dbg!(&self);
@@ -378,25 +587,43 @@ impl Cpu {
             IPush(_) => {
                 let v1 =
                     (mask_bit_group(self.ir, 1) as u32) | (mask_bit_group(self.ir, 2) as u32) << 8;
-                *self.dram.get_mut(self.sp as usize).unwrap() = v1;
-                self.zero_flag = self.tr == 0;
-                self.sp += 1;
+                if self.push_value(address, v1) {
+                    self.zero_flag = v1 == 0;
+                }
             }
             Pop => {
-                // it is very possible to pop all the way through all of the ram to index 0, or beyond the maximum size of dram, not sure if this matters though.
-                self.sp -= 1;
-                self.or = *self.dram.get(self.sp as usize).unwrap();
-                *self.dram.get_mut(self.sp as usize).unwrap() = 0;
-                println!("Popped value: {}", self.or);
-                self.zero_flag = self.or == 0;
+                if let Some(value) = self.pop_value(address) {
+                    self.or = value;
+                    if self.trace {
+                        println!("Popped value: {}", self.or);
+                    }
+                    self.zero_flag = self.or == 0;
+                }
             }
             Add(_, _) => {
-                let (outcome, chk) = (*self.get_reg(mask_bit_group(self.ir, 1)))
-                    .overflowing_add(*self.get_reg(mask_bit_group(self.ir, 2)));
-                self.ov_flag = chk;
+                let v1 = *self.get_reg(mask_bit_group(self.ir, 1));
+                let v2 = *self.get_reg(mask_bit_group(self.ir, 2));
+                *self.get_reg(mask_bit_group(self.ir, 1)) = self.add_with_flags(v1, v2, false);
+                self.print_inpr_regs();
+            }
+            Inc(_) | Dec(_) => {
+                self.print_inpr_reg();
+                let v1 = *self.get_reg(mask_bit_group(self.ir, 1));
+                // carry is left alone, so a counter can be stepped in the middle of a multi-word addition
+                let carry = self.carry_flag;
+                let outcome = if matches!(inst, Inc(_)) {
+                    self.add_with_flags(v1, 1, false)
+                } else {
+                    self.sub_with_flags(v1, 1, false)
+                };
+                self.carry_flag = carry;
                 *self.get_reg(mask_bit_group(self.ir, 1)) = outcome;
+            }
+            Adc(_, _) => {
+                let v1 = *self.get_reg(mask_bit_group(self.ir, 1));
+                let v2 = *self.get_reg(mask_bit_group(self.ir, 2));
+                *self.get_reg(mask_bit_group(self.ir, 1)) = self.add_with_flags(v1, v2, self.carry_flag);
                 self.print_inpr_regs();
-                self.zero_flag = *self.get_reg(mask_bit_group(self.ir, 1)) == 0;
             }
             MoveR(_, _) => {
                 *self.get_reg(mask_bit_group(self.ir, 1)) =
@@ -410,52 +637,62 @@ impl Cpu {
                 let v2 = *self.get_reg(mask_bit_group(self.ir, 2));
                 self.cmp_num(v1, v2);
             }
-            JE(_) => {
-                if self.eq_flag {
-                    self.pc = self.tr;
-                }
+            IMoveL(_, _) => {
+                self.print_inpr_reg();
+                *self.get_reg(mask_bit_group(self.ir, 1)) = self.tr;
+                self.zero_flag = *self.get_reg(mask_bit_group(self.ir, 1)) == 0;
             }
-            JGT(_) => {
-                if self.gt_flag {
-                    self.pc = self.tr;
-                }
+            Sub(_, _) => {
+                let v1 = *self.get_reg(mask_bit_group(self.ir, 1));
+                let v2 = *self.get_reg(mask_bit_group(self.ir, 2));
+                *self.get_reg(mask_bit_group(self.ir, 1)) = self.sub_with_flags(v1, v2, false);
+                self.print_inpr_regs();
             }
-            JLT(_) => {
-                if self.lt_flag {
-                    self.pc = self.tr;
+            Sbb(_, _) => {
+                let v1 = *self.get_reg(mask_bit_group(self.ir, 1));
+                let v2 = *self.get_reg(mask_bit_group(self.ir, 2));
+                *self.get_reg(mask_bit_group(self.ir, 1)) = self.sub_with_flags(v1, v2, self.carry_flag);
+                self.print_inpr_regs();
+            }
+            JE(_) | JGT(_) | JLT(_) | JZ(_) | JOV(_) | JMP(_) | JGTS(_) | JLTS(_) | JGES(_) | JLES(_) | JNE(_)
+            | JNZ(_) | JGE(_) | JLE(_) | JNO(_) | JC(_) | JNC(_) => {
+                if self.jump_condition_met(mask_bit_group(self.ir, 0)) {
+                    self.jump(address, self.tr);
                 }
             }
-            JZ(_) => {
-                if self.zero_flag {
-                    self.pc = self.tr;
+            Loop(_) => {
+                self.cr = self.cr.wrapping_sub(1);
+                if self.cr != 0 {
+                    self.jump(address, self.tr);
                 }
             }
-            IMoveL(_, _) => {
+            JmpR(_) => {
                 self.print_inpr_reg();
-                *self.get_reg(mask_bit_group(self.ir, 1)) = self.tr;
-                self.zero_flag = *self.get_reg(mask_bit_group(self.ir, 1)) == 0;
+                let target = *self.get_reg(mask_bit_group(self.ir, 1));
+                self.jump(address, target);
             }
-            Sub(_, _) => {
-                let (outcome, chk) = (*self.get_reg(mask_bit_group(self.ir, 1)))
-                    .overflowing_sub(*self.get_reg(mask_bit_group(self.ir, 2)));
-                *self.get_reg(mask_bit_group(self.ir, 1)) = outcome;
-                self.ov_flag = chk;
-                self.zero_flag = *self.get_reg(mask_bit_group(self.ir, 1)) == 0;
-                self.print_inpr_regs();
+            Call(_) => {
+                if self.push_value(address, self.pc) {
+                    self.jump(address, self.tr);
+                }
             }
-            JOV(_) => {
-                if self.ov_flag {
-                    self.pc = self.tr;
+            CallR(_) => {
+                self.print_inpr_reg();
+                let target = *self.get_reg(mask_bit_group(self.ir, 1));
+                if self.push_value(address, self.pc) {
+                    self.jump(address, target);
                 }
             }
-            JMP(_) => {
-                self.pc = self.tr;
+            Ret => {
+                if let Some(target) = self.pop_value(address) {
+                    self.jump(address, target);
+                }
             }
             ICmp(_, _) => {
                 self.print_inpr_reg();
                 let v1 = *self.get_reg(mask_bit_group(self.ir, 1));
                 let v2 =
-                    (mask_bit_group(self.ir, 2) as u32) | (mask_bit_group(self.ir, 3) as u32) << 16;
+                    (mask_bit_group(self.ir, 2) as u32) | (mask_bit_group(self.ir, 3) as u32) << 8;
                 self.cmp_num(v1, v2);
             }
             ICmpL(_, _) => {
@@ -463,17 +700,31 @@ impl Cpu {
                 let v1 = *self.get_reg(mask_bit_group(self.ir, 1));
                 self.cmp_num(v1, self.tr);
             }
+            Cmps(_, _) => {
+                self.print_inpr_regs();
+                let v1 = *self.get_reg(mask_bit_group(self.ir, 1));
+                let v2 = *self.get_reg(mask_bit_group(self.ir, 2));
+                self.cmp_signed(v1, v2);
+            }
+            ICmps(_, _) => {
+                self.print_inpr_reg();
+                let v1 = *self.get_reg(mask_bit_group(self.ir, 1));
+                // the immediate is 16 bits, and is sign extended to 32 bits
+                let v2 = ((mask_bit_group(self.ir, 2) as u16) | (mask_bit_group(self.ir, 3) as u16) << 8) as i16;
+                self.cmp_signed(v1, v2 as u32);
+            }
             IPushL(_) => {
-                *self.dram.get_mut(self.sp as usize).unwrap() = self.tr;
-                self.zero_flag = self.tr == 0;
-                self.sp += 1;
+                if self.push_value(address, self.tr) {
+                    self.zero_flag = self.tr == 0;
+                }
             }
             Push(_) => {
                 let reg_id = mask_bit_group(self.ir, 1);
                 self.print_inpr_reg();
-                *self.dram.get_mut(self.sp as usize).unwrap() = *self.get_reg(reg_id);
-                self.zero_flag = *self.dram.get(self.sp as usize).unwrap() == 0;
-                self.sp += 1;
+                let value = *self.get_reg(reg_id);
+                if self.push_value(address, value) {
+                    self.zero_flag = value == 0;
+                }
             }
             DumpR(_) => {
                 let reg_id = mask_bit_group(self.ir, 1);
@@ -483,41 +734,296 @@ impl Cpu {
             Lea(_) => {
                 let location: u16 = (mask_bit_group(self.ir, 1) as u16)
                     | ((mask_bit_group(self.ir, 2) as u16) << 8);
-                self.or = *self.dram.get(location as usize).unwrap();
+                self.or = self.read_memory(address, location as u32);
             }
             MoveA(_, _) => {
                 let location: u16 =
-                    (mask_bit_group(self.ir, 1) as u16) | (mask_bit_group(self.ir, 2) as u16);
+                    (mask_bit_group(self.ir, 1) as u16) | ((mask_bit_group(self.ir, 2) as u16) << 8);
                 let val = *self.get_reg(mask_bit_group(self.ir, 3));
                 self.print_inpr_reg_specific(3);
-                *self.dram.get_mut(location as usize).unwrap() = val;
+                self.write_memory(address, location as u32, val);
             }
             LeaR(_) => {
-                let address = *self.get_reg(mask_bit_group(self.ir, 1));
+                let location = *self.get_reg(mask_bit_group(self.ir, 1));
+                self.print_inpr_reg();
+                self.or = self.read_memory(address, location);
+            }
+            LeaO(_, _) => {
+                self.print_inpr_reg();
+                let location = self.offset_address(mask_bit_group(self.ir, 1), mask_bit_group(self.ir, 2));
+                self.or = self.read_memory(address, location);
+            }
+            Ldb(_, _, _) | Ldbs(_, _, _) | Ldh(_, _, _) | Ldhs(_, _, _) => {
+                self.print_inpr_regs();
+                let byte_address = self.offset_address(mask_bit_group(self.ir, 2), mask_bit_group(self.ir, 3));
+                let value = match inst {
+                    Ldb(_, _, _) => self.read_byte(address, byte_address),
+                    Ldbs(_, _, _) => self.read_byte(address, byte_address) as u8 as i8 as u32,
+                    Ldh(_, _, _) => self.read_half(address, byte_address),
+                    _ => self.read_half(address, byte_address) as u16 as i16 as u32,
+                };
+                *self.get_reg(mask_bit_group(self.ir, 1)) = value;
+            }
+            Stb(_, _, _) | Sth(_, _, _) => {
+                self.print_inpr_reg();
+                self.print_inpr_reg_specific(3);
+                let byte_address = self.offset_address(mask_bit_group(self.ir, 1), mask_bit_group(self.ir, 2));
+                let value = *self.get_reg(mask_bit_group(self.ir, 3));
+                self.write_byte(address, byte_address, value as u8);
+                if matches!(inst, Sth(_, _, _)) {
+                    self.write_byte(address, byte_address.wrapping_add(1), (value >> 8) as u8);
+                }
+            }
+            MoveAR(_, _) => {
+                self.print_inpr_regs();
+                let location = *self.get_reg(mask_bit_group(self.ir, 1));
+                let val = *self.get_reg(mask_bit_group(self.ir, 2));
+                self.write_memory(address, location, val);
+            }
+            MoveAO(_, _, _) => {
                 self.print_inpr_reg();
-                self.or = *self.dram.get(address as usize).unwrap();
+                self.print_inpr_reg_specific(3);
+                let location = self.offset_address(mask_bit_group(self.ir, 1), mask_bit_group(self.ir, 2));
+                let val = *self.get_reg(mask_bit_group(self.ir, 3));
+                self.write_memory(address, location, val);
             }
-            Shl(_, _) => {
+            Shl(_, _) | Shr(_, _) | Sar(_, _) | Rol(_, _) | Ror(_, _) => {
                 self.print_inpr_reg();
-                let ir = self.ir;
-                let reg = self.get_reg(mask_bit_group(self.ir, 1));
-                *reg = reg.checked_shl(mask_bit_group(ir, 2) as u32).unwrap_or(0);
-                self.zero_flag = *reg == 0;
+                let value = *self.get_reg(mask_bit_group(self.ir, 1));
+                let outcome = self.shift_with_flags(&inst, value, mask_bit_group(self.ir, 2) as u32);
+                *self.get_reg(mask_bit_group(self.ir, 1)) = outcome;
             }
-            Shr(_, _) => {
+            Bt(_, _) | Bts(_, _) | Btr(_, _) | Btc(_, _) | IBt(_, _) | IBts(_, _) | IBtr(_, _) | IBtc(_, _) => {
                 self.print_inpr_reg();
-                let ir = self.ir;
-                let reg = self.get_reg(mask_bit_group(self.ir, 1));
-                *reg = reg.checked_shr(mask_bit_group(ir, 2) as u32).unwrap_or(0);
-                self.zero_flag = *reg == 0;
+                let value = *self.get_reg(mask_bit_group(self.ir, 1));
+                let index = match inst {
+                    Bt(_, _) | Bts(_, _) | Btr(_, _) | Btc(_, _) => *self.get_reg(mask_bit_group(self.ir, 2)),
+                    _ => mask_bit_group(self.ir, 2) as u32,
+                };
+                // the index wraps round, so bit 32 is bit 0
+                let bit = 1u32 << (index % 32);
+                self.carry_flag = value & bit != 0;
+                *self.get_reg(mask_bit_group(self.ir, 1)) = match inst {
+                    Bts(_, _) | IBts(_, _) => value | bit,
+                    Btr(_, _) | IBtr(_, _) => value & !bit,
+                    Btc(_, _) | IBtc(_, _) => value ^ bit,
+                    _ => value,
+                };
+            }
+            Popcnt(_, _) | Clz(_, _) | Ctz(_, _) => {
+                self.print_inpr_regs();
+                let value = *self.get_reg(mask_bit_group(self.ir, 2));
+                let count = match inst {
+                    Popcnt(_, _) => value.count_ones(),
+                    Clz(_, _) => value.leading_zeros(),
+                    _ => value.trailing_zeros(),
+                };
+                self.carry_flag = value == 0;
+                self.zero_flag = count == 0;
+                *self.get_reg(mask_bit_group(self.ir, 1)) = count;
+            }
+            ShlR(_, _) | ShrR(_, _) | SarR(_, _) | RolR(_, _) | RorR(_, _) => {
+                self.print_inpr_regs();
+                let value = *self.get_reg(mask_bit_group(self.ir, 1));
+                let amount = *self.get_reg(mask_bit_group(self.ir, 2));
+                let outcome = self.shift_with_flags(&inst, value, amount);
+                *self.get_reg(mask_bit_group(self.ir, 1)) = outcome;
             }
         }
-        println!();
+        // pc can be written like any other register, and can run off the end of dram, both are jumps outside of it
+        if !is_unknown && self.fault.is_none() && self.pc >= DRAM_SIZE {
+            self.fault = Some(Fault::JumpOutOfMemory { address, target: self.pc });
+        }
+        if self.trace {
+            println!();
+        }
+    }
+
+    /// Move pc to the target of the jump at `address`, faulting instead if the target is outside of dram
+    fn jump(&mut self, address: u32, target: u32) {
+        if target >= DRAM_SIZE {
+            self.fault = Some(Fault::JumpOutOfMemory { address, target });
+        } else {
+            self.pc = target;
+        }
+    }
+
+    /// Read the dram value at `target` for the instruction at `address`, faulting instead if `target` is outside of dram
+    fn read_memory(&mut self, address: u32, target: u32) -> u32 {
+        match self.dram.get(target as usize) {
+            Some(value) => *value,
+            None => {
+                self.fault = Some(Fault::AccessOutOfMemory { address, target });
+                0
+            }
+        }
+    }
+
+    /// Write `value` to dram at `target` for the instruction at `address`, faulting instead if `target` is outside of dram
+    fn write_memory(&mut self, address: u32, target: u32, value: u32) {
+        match self.dram.get_mut(target as usize) {
+            Some(slot) => *slot = value,
+            None => self.fault = Some(Fault::AccessOutOfMemory { address, target }),
+        }
+    }
+
+    /// Read the byte at `byte_address` for the instruction at `address`, bytes are numbered four to a word, little endian,
+    /// so byte 0 is the lowest byte of word 0 and byte 4 the lowest byte of word 1, the same order as `to_binary`
+    fn read_byte(&mut self, address: u32, byte_address: u32) -> u32 {
+        let word = self.read_memory(address, byte_address / 4);
+        (word >> (byte_address % 4 * 8)) & 0xFF
+    }
+
+    /// Read the little endian half word at `byte_address`, which may start in one word and end in the next
+    fn read_half(&mut self, address: u32, byte_address: u32) -> u32 {
+        self.read_byte(address, byte_address) | self.read_byte(address, byte_address.wrapping_add(1)) << 8
+    }
+
+    /// Write a byte at `byte_address` for the instruction at `address`, leaving the other bytes of its word alone
+    fn write_byte(&mut self, address: u32, byte_address: u32, value: u8) {
+        let target = byte_address / 4;
+        let shift = byte_address % 4 * 8;
+        let word = self.read_memory(address, target);
+        self.write_memory(address, target, (word & !(0xFF << shift)) | (value as u32) << shift);
     }
 
-    /// Compare both input numbers and assign flag states
+    /// The address held in register `base_reg` plus the signed 8 bit `offset`, as used by `[reg + offset]` operands
+    fn offset_address(&mut self, base_reg: u8, offset: u8) -> u32 {
+        self.get_reg(base_reg).wrapping_add(offset as i8 as i32 as u32)
+    }
+
+    /// Push a value onto the stack for the instruction at `address`, faulting instead if sp is not inside dram.
+    /// Returns true if the value was pushed
+    fn push_value(&mut self, address: u32, value: u32) -> bool {
+        match self.dram.get_mut(self.sp as usize) {
+            Some(slot) => {
+                *slot = value;
+                self.sp += 1;
+                true
+            }
+            None => {
+                self.fault = Some(Fault::StackOverflow { address, sp: self.sp });
+                false
+            }
+        }
+    }
+
+    /// Pop a value off the stack for the instruction at `address`, clearing the word it was in,
+    /// faulting instead if sp is at or below the base of the stack, or past the end of dram
+    fn pop_value(&mut self, address: u32) -> Option<u32> {
+        if self.sp <= self.stack_base {
+            self.fault = Some(Fault::StackUnderflow { address, sp: self.sp });
+            return None;
+        }
+        match self.dram.get_mut(self.sp as usize - 1) {
+            Some(slot) => {
+                let value = std::mem::take(slot);
+                self.sp -= 1;
+                Some(value)
+            }
+            None => {
+                self.fault = Some(Fault::StackUnderflow { address, sp: self.sp });
+                None
+            }
+        }
+    }
+
+    /// Returns true if the condition of a jump opcode is met by the current flags, see `JUMP_CONDITIONS`
+    fn jump_condition_met(&self, op_code: u8) -> bool {
+        JUMP_CONDITIONS
+            .iter()
+            .find(|(jump, _)| *jump == op_code)
+            .is_some_and(|(_, condition)| condition(self))
+    }
+
+    /// Compare both input numbers as unsigned numbers and assign flag states
+    /// The zero, sign, carry and overflow flags are set as if `num2` was subtracted from `num1`
     fn cmp_num(&mut self, num1: u32, num2: u32) {
-        match (num1).cmp(&num2) {
+        self.sub_with_flags(num1, num2, false);
+        self.set_order_flags(num1.cmp(&num2));
+    }
+
+    /// Compare both input numbers as signed numbers and assign flag states
+    /// The zero, sign, carry and overflow flags are set as if `num2` was subtracted from `num1`
+    fn cmp_signed(&mut self, num1: u32, num2: u32) {
+        self.sub_with_flags(num1, num2, false);
+        self.set_order_flags((num1 as i32).cmp(&(num2 as i32)));
+    }
+
+    /// Add two numbers and a carry in, setting the zero, sign, carry and overflow flags from the result
+    fn add_with_flags(&mut self, num1: u32, num2: u32, carry_in: bool) -> u32 {
+        // the sum is worked out wider than 32 bits, so the carry and signed overflow can be seen
+        let unsigned = num1 as u64 + num2 as u64 + carry_in as u64;
+        let signed = num1 as i32 as i64 + num2 as i32 as i64 + carry_in as i64;
+        let outcome = unsigned as u32;
+        self.carry_flag = unsigned > u32::MAX as u64;
+        self.ov_flag = signed != outcome as i32 as i64;
+        self.set_result_flags(outcome);
+        outcome
+    }
+
+    /// Subtract `num2` and a borrow in from `num1`, setting the zero, sign, carry and overflow flags from the result
+    /// Carry is set when the subtraction borrows, meaning `num2` and the borrow are larger than `num1` as unsigned numbers
+    fn sub_with_flags(&mut self, num1: u32, num2: u32, borrow_in: bool) -> u32 {
+        let unsigned = num1 as i64 - num2 as i64 - borrow_in as i64;
+        let signed = num1 as i32 as i64 - num2 as i32 as i64 - borrow_in as i64;
+        let outcome = unsigned as u32;
+        self.carry_flag = unsigned < 0;
+        self.ov_flag = signed != outcome as i32 as i64;
+        self.set_result_flags(outcome);
+        outcome
+    }
+
+    /// Shift or rotate `value` by `amount` bits as the given shift instruction, setting carry to the last bit shifted out.
+    /// Shifts also set the zero and sign flags from the result, rotates only set carry,
+    /// and a shift or rotate by 0 leaves the value and every flag alone
+    fn shift_with_flags(&mut self, inst: &Instruction, value: u32, amount: u32) -> u32 {
+        if amount == 0 {
+            return value;
+        }
+        match inst {
+            Shl(_, _) | ShlR(_, _) => {
+                let outcome = value.checked_shl(amount).unwrap_or(0);
+                self.carry_flag = amount <= 32 && (value >> (32 - amount)) & 1 == 1;
+                self.set_result_flags(outcome);
+                outcome
+            }
+            Shr(_, _) | ShrR(_, _) => {
+                let outcome = value.checked_shr(amount).unwrap_or(0);
+                self.carry_flag = amount <= 32 && (value >> (amount - 1)) & 1 == 1;
+                self.set_result_flags(outcome);
+                outcome
+            }
+            Sar(_, _) | SarR(_, _) => {
+                // shifting by 32 or more fills every bit with the sign bit
+                let signed = value as i32;
+                let outcome = signed.checked_shr(amount).unwrap_or(signed >> 31) as u32;
+                self.carry_flag = (signed >> (amount - 1).min(31)) & 1 == 1;
+                self.set_result_flags(outcome);
+                outcome
+            }
+            Rol(_, _) | RolR(_, _) => {
+                let outcome = value.rotate_left(amount);
+                self.carry_flag = outcome & 1 == 1;
+                outcome
+            }
+            _ => {
+                let outcome = value.rotate_right(amount);
+                self.carry_flag = outcome >> 31 == 1;
+                outcome
+            }
+        }
+    }
+
+    /// Set the zero and sign flags from the result of an operation
+    fn set_result_flags(&mut self, result: u32) {
+        self.zero_flag = result == 0;
+        self.sign_flag = (result as i32) < 0;
+    }
+
+    /// Set the less than, equal and greater than flags from the order of two compared numbers
+    fn set_order_flags(&mut self, ordering: Ordering) {
+        match ordering {
             Ordering::Less => {
                 self.lt_flag = true;
                 self.eq_flag = false;
@@ -546,6 +1052,7 @@ impl Cpu {
             SP => &mut self.sp,
             TR => &mut self.tr,
             CR => &mut self.cr,
+            R0..=R15 => &mut self.r[(reg - R0) as usize],
             _ => {
                 self.dump();
                 panic!("unexpected register input: {}", reg);
@@ -572,8 +1079,12 @@ impl Cpu {
     // }
 
     /// Execute a specific number of cycles
+    /// Stops early if the program faults
     pub fn execute_cycles(&mut self, cycle_count: usize) {
         for _ in 0..cycle_count {
+            if self.fault.is_some() {
+                return;
+            }
             let inst = self.fetch();
             self.execute(inst);
         }
@@ -581,12 +1092,23 @@ impl Cpu {
 
     /// Run the cpu dram until there is an unknown instruction
     pub fn execute_until_unknown(&mut self) {
+        self.execute_until_unknown_or_limit(None);
+    }
+
+    /// Run the cpu dram until there is an unknown instruction, or until the given number of instructions have been executed.
+    /// Returns true if the program reached an unknown instruction or a fault, false if it ran out of cycles first
+    pub fn execute_until_unknown_or_limit(&mut self, max_cycles: Option<usize>) -> bool {
+        let mut cycles = 0;
         loop {
+            if max_cycles.is_some_and(|max_cycles| cycles >= max_cycles) {
+                return false;
+            }
             let inst = self.fetch();
             let cont = matches!(inst, Instruction::Unknown);
             self.execute(inst);
-            if cont {
-                break;
+            cycles += 1;
+            if cont || self.fault.is_some() {
+                return true;
             }
         }
     }
@@ -618,6 +1140,9 @@ impl Cpu {
             CR => {
                 println!("cr: {0:#034b} : {0:#X} : {0}", self.cr);
             }
+            R0..=R15 => {
+                println!("r{1}: {0:#034b} : {0:#X} : {0}", self.r[(reg - R0) as usize], reg - R0);
+            }
             _ => {
                 println!("Unexpected reg dump");
                 dbg!(reg);
@@ -638,14 +1163,29 @@ impl Cpu {
         // println!("inpr2: {0:#034b} : {0:#X} : {0}", self.inpr2);
         println!("sp: {0:#034b} : {0:#X} : {0}", self.sp);
         println!("tr: {0:#034b} : {0:#X} : {0}", self.tr);
+        for (index, value) in self.r.iter().enumerate() {
+            println!("r{index}: {value:#034b} : {value:#X} : {value}");
+        }
         // print flags
         println!("Zero flag: {}", self.zero_flag);
         println!("LT flag: {}", self.lt_flag);
         println!("GT flag: {}", self.gt_flag);
         println!("EQ flag: {}", self.eq_flag);
         println!("OV flag: {}", self.ov_flag);
+        println!("Sign flag: {}", self.sign_flag);
+        println!("Carry flag: {}", self.carry_flag);
 
         // print out dram
+        for line in self.disassemble() {
+            println!("{line}");
+        }
+        println!();
+    }
+
+    /// Disassemble every used dram address into a line of text, giving the address, the value, and the value as an instruction,
+    /// followed by its label and source line if the cpu has debug info
+    pub fn disassemble(&self) -> Vec<String> {
+        let mut lines = vec![];
         for (index, data) in self.dram.iter().enumerate() {
             // convert each line in dram into text in the form of an instruction
             let inst_text = {
@@ -666,7 +1206,8 @@ impl Cpu {
                         format!("{}", self.dram.get(index + 1).unwrap())
                     }
                     // single 16 bit literal parse group
-                    JE(_) | JGT(_) | JLT(_) | JZ(_) | JOV(_) | JMP(_) | Lea(_) => {
+                    JE(_) | JGT(_) | JLT(_) | JZ(_) | JOV(_) | JMP(_) | JGTS(_) | JLTS(_) | JGES(_)
+                    | JLES(_) | JNE(_) | JNZ(_) | JGE(_) | JLE(_) | JNO(_) | JC(_) | JNC(_) | Loop(_) | Call(_) | Lea(_) => {
                         format!(
                             "{}",
                             ((mask_bit_group(*data, 1) as u16)
@@ -674,14 +1215,16 @@ impl Cpu {
                         )
                     }
                     // one literal u8 parse group
-                    ISub(_) | IAdd(_) | IPush(_) => {
+                    ISub(_) | IAdd(_) | IAdc(_) | ISbb(_) | IPush(_) => {
                         format!(
                             "{}",
                             (mask_bit_group(*data, 2) as u16) // | ((mask_bit_group(*data, 2) as u16) << 8)
                         )
                     }
                     // two register parse group
-                    Sub(_, _) | Add(_, _) | Cmp(_, _) | MoveR(_, _) => {
+                    Sub(_, _) | Add(_, _) | Adc(_, _) | Sbb(_, _) | Cmp(_, _) | Cmps(_, _) | MoveR(_, _) | ShlR(_, _)
+                    | ShrR(_, _) | SarR(_, _) | RolR(_, _) | RorR(_, _) | Bt(_, _) | Bts(_, _) | Btr(_, _) | Btc(_, _)
+                    | Popcnt(_, _) | Clz(_, _) | Ctz(_, _) => {
                         format!(
                             "{} {}",
                             get_name_from_reg_id(mask_bit_group(*data, 1))
@@ -691,18 +1234,26 @@ impl Cpu {
                         )
                     }
                     // no args parse group
-                    Pop | Dump | Unknown => "".to_string(),
+                    Pop | Dump | Ret | Unknown => "".to_string(),
                     // one register one 16 bit literal parse group
                     ICmp(_, _) => {
                         format!(
                             "{} {}",
-                            get_name_from_reg_id(mask_bit_group(*data, 1)).unwrap(),
+                            get_name_from_reg_id(mask_bit_group(*data, 1)).unwrap_or("UNKNOWN".to_string()),
                             (mask_bit_group(*data, 2) as u32)
                                 | (mask_bit_group(*data, 3) as u32) << 8
                         )
                     }
+                    // one register one signed 16 bit literal parse group
+                    ICmps(_, _) => {
+                        format!(
+                            "{} {}",
+                            get_name_from_reg_id(mask_bit_group(*data, 1)).unwrap_or("UNKNOWN".to_string()),
+                            ((mask_bit_group(*data, 2) as u16) | (mask_bit_group(*data, 3) as u16) << 8) as i16
+                        )
+                    }
                     // single register only parse group
-                    Push(_) | DumpR(_) | LeaR(_) => get_name_from_reg_id(mask_bit_group(*data, 1))
+                    Push(_) | DumpR(_) | LeaR(_) | JmpR(_) | CallR(_) | Inc(_) | Dec(_) => get_name_from_reg_id(mask_bit_group(*data, 1))
                         .unwrap_or("UNKNOWN".to_string())
                         .to_string(),
                     MoveA(_, _) => {
@@ -713,10 +1264,34 @@ impl Cpu {
                             mask_bit_group(*data, 3)
                         )
                     }
-                    Shr(_, _) | Shl(_, _) => {
+                    // one register plus a signed offset parse group
+                    LeaO(_, _) => format_memory_operand(mask_bit_group(*data, 1), mask_bit_group(*data, 2)),
+                    MoveAO(_, _, _) | Stb(_, _, _) | Sth(_, _, _) => {
                         format!(
                             "{} {}",
-                            get_name_from_reg_id(mask_bit_group(*data, 1)).unwrap(),
+                            format_memory_operand(mask_bit_group(*data, 1), mask_bit_group(*data, 2)),
+                            get_name_from_reg_id(mask_bit_group(*data, 3)).unwrap_or("UNKNOWN".to_string())
+                        )
+                    }
+                    Ldb(_, _, _) | Ldbs(_, _, _) | Ldh(_, _, _) | Ldhs(_, _, _) => {
+                        format!(
+                            "{} {}",
+                            get_name_from_reg_id(mask_bit_group(*data, 1)).unwrap_or("UNKNOWN".to_string()),
+                            format_memory_operand(mask_bit_group(*data, 2), mask_bit_group(*data, 3))
+                        )
+                    }
+                    MoveAR(_, _) => {
+                        format!(
+                            "{} {}",
+                            get_name_from_reg_id(mask_bit_group(*data, 1)).unwrap_or("UNKNOWN".to_string()),
+                            get_name_from_reg_id(mask_bit_group(*data, 2)).unwrap_or("UNKNOWN".to_string())
+                        )
+                    }
+                    Shr(_, _) | Shl(_, _) | Sar(_, _) | Rol(_, _) | Ror(_, _) | IBt(_, _) | IBts(_, _) | IBtr(_, _)
+                    | IBtc(_, _) => {
+                        format!(
+                            "{} {}",
+                            get_name_from_reg_id(mask_bit_group(*data, 1)).unwrap_or("UNKNOWN".to_string()),
                             mask_bit_group(*data, 2)
                         )
                     }
@@ -735,9 +1310,224 @@ impl Cpu {
                 || (index >= (DRAM_SIZE - (DRAM_SIZE / 4)) as usize && index < self.sp as usize)
             {
                 // print each dram address giving the index, the value in binary, the value in decimal, then hexidecimal, then as instruction text
-                println!("[{index}] = {:#034b} : {0} : {0:#X} : {}", data, inst_text);
+                lines.push(match self.debug_info.annotate(index as u32) {
+                    Some(annotation) => format!(
+                        "[{index}] = {:#034b} : {0} : {0:#X} : {} ; {annotation}",
+                        data, inst_text
+                    ),
+                    None => format!("[{index}] = {:#034b} : {0} : {0:#X} : {}", data, inst_text),
+                });
             }
         }
-        println!();
+        lines
+    }
+}
+
+/// Format a base register and signed offset as a memory operand, e.g. `[SP - 1]`
+fn format_memory_operand(base_reg: u8, offset: u8) -> String {
+    let base = get_name_from_reg_id(base_reg).unwrap_or("UNKNOWN".to_string());
+    match offset as i8 {
+        0 => format!("[{base}]"),
+        offset if offset < 0 => format!("[{base} - {}]", offset.unsigned_abs()),
+        offset => format!("[{base} + {offset}]"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a program from address 0 until it reaches the end of its instructions
+    fn run(program: &[Instruction]) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.set_trace(false);
+        for inst in program {
+            cpu.add_to_end(inst);
+        }
+        cpu.execute_until_unknown();
+        cpu
+    }
+
+    /// The zero, sign, carry and overflow flags, in that order
+    fn arithmetic_flags(cpu: &Cpu) -> (bool, bool, bool, bool) {
+        (cpu.zero_flag, cpu.sign_flag, cpu.carry_flag, cpu.ov_flag)
+    }
+
+    #[test]
+    fn add_carry_out_sets_carry_but_not_overflow() {
+        let cpu = run(&[IMoveL(ACC, u32::MAX), IAdd(1)]);
+        assert_eq!(cpu.acc, 0);
+        assert_eq!(arithmetic_flags(&cpu), (true, false, true, false));
+    }
+
+    #[test]
+    fn add_signed_overflow_sets_overflow_and_sign_but_not_carry() {
+        let cpu = run(&[IMoveL(ACC, i32::MAX as u32), IAdd(1)]);
+        assert_eq!(cpu.acc, 0x8000_0000);
+        assert_eq!(arithmetic_flags(&cpu), (false, true, false, true));
+    }
+
+    #[test]
+    fn sub_borrow_sets_carry_and_sign_but_not_overflow() {
+        let cpu = run(&[IMoveL(ACC, 1), IMoveL(CR, 2), Sub(ACC, CR)]);
+        assert_eq!(cpu.acc, u32::MAX);
+        assert_eq!(arithmetic_flags(&cpu), (false, true, true, false));
+    }
+
+    #[test]
+    fn sub_signed_overflow_sets_overflow_but_not_carry() {
+        let cpu = run(&[IMoveL(ACC, i32::MIN as u32), ISub(1)]);
+        assert_eq!(cpu.acc, i32::MAX as u32);
+        assert_eq!(arithmetic_flags(&cpu), (false, false, false, true));
+    }
+
+    #[test]
+    fn cmp_sets_flags_as_a_subtraction_without_changing_registers() {
+        let cpu = run(&[IMoveL(ACC, 1), IMoveL(CR, 2), Cmp(ACC, CR)]);
+        assert_eq!((cpu.acc, cpu.cr), (1, 2));
+        assert_eq!((cpu.lt_flag, cpu.eq_flag, cpu.gt_flag), (true, false, false));
+        assert_eq!(arithmetic_flags(&cpu), (false, true, true, false));
+
+        let cpu = run(&[IMoveL(ACC, 7), ICmp(ACC, 7)]);
+        assert_eq!((cpu.lt_flag, cpu.eq_flag, cpu.gt_flag), (false, true, false));
+        assert_eq!(arithmetic_flags(&cpu), (true, false, false, false));
+    }
+
+    #[test]
+    fn cmp_is_unsigned_and_cmps_is_signed() {
+        let cpu = run(&[IMoveL(ACC, -3i32 as u32), IMoveL(CR, 1), Cmp(ACC, CR)]);
+        assert_eq!((cpu.lt_flag, cpu.gt_flag), (false, true));
+
+        let cpu = run(&[IMoveL(ACC, -3i32 as u32), IMoveL(CR, 1), Cmps(ACC, CR)]);
+        assert_eq!((cpu.lt_flag, cpu.gt_flag), (true, false));
+
+        let cpu = run(&[IMoveL(ACC, -3i32 as u32), ICmps(ACC, -1)]);
+        assert_eq!((cpu.lt_flag, cpu.gt_flag), (true, false));
+    }
+
+    #[test]
+    fn cmps_overflow_does_not_change_the_signed_order() {
+        // i32::MIN - 1 overflows, but i32::MIN is still the smaller number
+        let cpu = run(&[IMoveL(ACC, i32::MIN as u32), IMoveL(CR, 1), Cmps(ACC, CR)]);
+        assert!(cpu.ov_flag);
+        assert!(cpu.jump_condition_met(crate::constants::JLTS));
+    }
+
+    /// Load two multi-word numbers, lowest word first, into r0.. and r8.., then add or subtract them a word at a time
+    fn multi_word(a: &[u32], b: &[u32], subtract: bool) -> Cpu {
+        let mut program = vec![];
+        for (index, (a, b)) in a.iter().zip(b).enumerate() {
+            program.push(IMoveL(R0 + index as u8, *a));
+            program.push(IMoveL(R0 + 8 + index as u8, *b));
+        }
+        for index in 0..a.len() as u8 {
+            let (a, b) = (R0 + index, R0 + 8 + index);
+            program.push(match (subtract, index) {
+                (false, 0) => Add(a, b),
+                (false, _) => Adc(a, b),
+                (true, 0) => Sub(a, b),
+                (true, _) => Sbb(a, b),
+            });
+        }
+        run(&program)
+    }
+
+    #[test]
+    fn adc_chain_adds_64_bit_numbers() {
+        // 0x1_FFFFFFFF + 0x2_00000001 = 0x4_00000000
+        let cpu = multi_word(&[u32::MAX, 1], &[1, 2], false);
+        assert_eq!(cpu.r[..2], [0, 4]);
+        assert!(!cpu.carry_flag);
+    }
+
+    #[test]
+    fn adc_chain_adds_96_bit_numbers_carrying_through_every_word() {
+        // 0x0_FFFFFFFF_FFFFFFFF + 1 = 0x1_00000000_00000000
+        let cpu = multi_word(&[u32::MAX, u32::MAX, 0], &[1, 0, 0], false);
+        assert_eq!(cpu.r[..3], [0, 0, 1]);
+
+        // a carry out of the top word is left in the carry flag
+        let cpu = multi_word(&[u32::MAX, u32::MAX, u32::MAX], &[1, 0, 0], false);
+        assert_eq!(cpu.r[..3], [0, 0, 0]);
+        assert!(cpu.carry_flag);
+    }
+
+    #[test]
+    fn sbb_chain_subtracts_64_bit_numbers() {
+        // 0x4_00000000 - 0x2_00000001 = 0x1_FFFFFFFF
+        let cpu = multi_word(&[0, 4], &[1, 2], true);
+        assert_eq!(cpu.r[..2], [u32::MAX, 1]);
+        assert!(!cpu.carry_flag);
+    }
+
+    #[test]
+    fn sbb_chain_subtracts_96_bit_numbers_borrowing_through_every_word() {
+        // 0x1_00000000_00000000 - 1 = 0x0_FFFFFFFF_FFFFFFFF
+        let cpu = multi_word(&[0, 0, 1], &[1, 0, 0], true);
+        assert_eq!(cpu.r[..3], [u32::MAX, u32::MAX, 0]);
+
+        // subtracting a larger number leaves a borrow in the carry flag
+        let cpu = multi_word(&[0, 0, 0], &[1, 0, 0], true);
+        assert_eq!(cpu.r[..3], [u32::MAX, u32::MAX, u32::MAX]);
+        assert!(cpu.carry_flag);
+    }
+
+    #[test]
+    fn immediate_adc_and_sbb_use_the_carry_flag() {
+        let cpu = run(&[IMoveL(ACC, u32::MAX), IAdd(1), IAdc(5)]);
+        assert_eq!(cpu.acc, 6);
+        let cpu = run(&[Sub(ACC, ACC), ISub(1), IMoveL(ACC, 10), ISbb(5)]);
+        assert_eq!(cpu.acc, 4);
+    }
+
+    #[test]
+    fn push_with_a_full_stack_faults() {
+        let set_sp = IMoveL(SP, DRAM_SIZE);
+        let cpu = run(&[set_sp.clone(), IMoveL(ACC, 5), Push(ACC)]);
+        let address = set_sp.to_instruction_data().len() as u32 * 2;
+        assert_eq!(cpu.get_fault(), Some(&Fault::StackOverflow { address, sp: DRAM_SIZE }));
+        assert_eq!(cpu.get_sp(), DRAM_SIZE);
+    }
+
+    #[test]
+    fn ret_with_an_empty_stack_faults() {
+        let set_acc = IMoveL(ACC, 1);
+        let cpu = run(&[set_acc.clone(), Ret]);
+        let address = set_acc.to_instruction_data().len() as u32;
+        let sp = Cpu::default().get_sp();
+        assert_eq!(cpu.get_fault(), Some(&Fault::StackUnderflow { address, sp }));
+        assert_eq!(cpu.get_sp(), sp);
+    }
+
+    #[test]
+    fn pop_does_not_reach_the_variables_below_the_stack() {
+        let mut cpu = Cpu::new();
+        cpu.set_trace(false);
+        let variable = cpu.push_variable(7);
+        cpu.write_dram(0, &IPush(5).to_instruction_data());
+        cpu.write_dram(1, &Pop.to_instruction_data());
+        cpu.write_dram(2, &Pop.to_instruction_data());
+        cpu.execute_until_unknown();
+        assert_eq!(cpu.or, 5);
+        assert_eq!(cpu.get_fault(), Some(&Fault::StackUnderflow { address: 2, sp: variable + 1 }));
+        assert_eq!(cpu.get_dram()[variable as usize], 7);
+    }
+
+    #[test]
+    fn move_into_pc_outside_of_dram_faults() {
+        let set_acc = IMoveL(ACC, 1000);
+        let cpu = run(&[set_acc.clone(), MoveR(PC, ACC)]);
+        let address = set_acc.to_instruction_data().len() as u32;
+        assert_eq!(cpu.get_fault(), Some(&Fault::JumpOutOfMemory { address, target: 1000 }));
+    }
+
+    #[test]
+    fn running_off_the_end_of_dram_faults() {
+        let mut cpu = Cpu::new();
+        cpu.set_trace(false);
+        cpu.write_dram(DRAM_SIZE - 1, &IMoveL(ACC, 5).to_instruction_data()[..1]);
+        cpu.pc = DRAM_SIZE - 1;
+        cpu.execute_until_unknown();
+        assert_eq!(cpu.get_fault(), Some(&Fault::JumpOutOfMemory { address: DRAM_SIZE - 1, target: DRAM_SIZE }));
     }
 }
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// First line of debug info text, followed by the format version
+pub const DEBUG_INFO_HEADER: &str = "CRDBG 1";
+
+/// The place in the source code an instruction was assembled from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceInfo {
+    pub file: String,
+    /// Line number in the file, starting at 1
+    pub line: usize,
+    /// Column the instruction starts at, starting at 1
+    pub column: usize,
+    /// The source line, without surrounding whitespace
+    pub text: String,
+}
+
+impl Display for SourceInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.text)
+    }
+}
+
+/// The addresses that belong to a label, from the label up to the next label it contains or is contained by, `start..end`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LabelRange {
+    pub name: String,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// Information the assembler leaves for the emulator and disassembler, so addresses can be shown as source code
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    /// Source line of every instruction, by the address of its first word
+    pub lines: BTreeMap<u32, SourceInfo>,
+    pub labels: Vec<LabelRange>,
+    /// Address of every variable by name
+    pub variables: BTreeMap<String, u32>,
+}
+
+impl DebugInfo {
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.labels.is_empty() && self.variables.is_empty()
+    }
+
+    /// Describe an address relative to the innermost label it belongs to, e.g. `main.loop+2`
+    pub fn describe_address(&self, address: u32) -> Option<String> {
+        let scope = self
+            .labels
+            .iter()
+            .filter(|scope| scope.start <= address && address < scope.end)
+            .max_by_key(|scope| scope.start)?;
+        Some(match address - scope.start {
+            0 => scope.name.clone(),
+            offset => format!("{}+{offset}", scope.name),
+        })
+    }
+
+    /// Name of the variable stored at an address
+    pub fn variable_at(&self, address: u32) -> Option<&str> {
+        self.variables
+            .iter()
+            .find(|(_, variable_address)| **variable_address == address)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Everything known about an address as a single line of text, e.g. `main.loop+1 code.cr:4:5: add 1`
+    pub fn annotate(&self, address: u32) -> Option<String> {
+        if let Some(name) = self.variable_at(address) {
+            return Some(format!("variable {name}"));
+        }
+        let source = self.lines.get(&address);
+        match (self.describe_address(address), source) {
+            (Some(label), Some(source)) => Some(format!("{label} {source}")),
+            (None, Some(source)) => Some(source.to_string()),
+            (Some(label), None) => Some(label),
+            (None, None) => None,
+        }
+    }
+
+    /// Convert the debug info into its text format
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{DEBUG_INFO_HEADER}\n");
+        for (address, source) in &self.lines {
+            // the file name and text can contain spaces, so they are split by a tab and come last
+            text += &format!(
+                "line {address} {} {} {}\t{}\n",
+                source.line, source.column, source.file, source.text
+            );
+        }
+        for scope in &self.labels {
+            text += &format!("label {} {} {}\n", scope.start, scope.end, scope.name);
+        }
+        for (name, address) in &self.variables {
+            text += &format!("variable {address} {name}\n");
+        }
+        text
+    }
+
+    /// Read debug info from its text format
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(DEBUG_INFO_HEADER) {
+            return Err("not cr_cpu debug info".to_string());
+        }
+
+        let invalid = |line: &str| format!("invalid line in debug info: {line}");
+        let mut info = DebugInfo::default();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let (kind, rest) = line.split_once(' ').ok_or_else(|| invalid(line))?;
+            match kind {
+                "line" => {
+                    let (numbers, source_text) = rest.split_once('\t').ok_or_else(|| invalid(line))?;
+                    let mut items = numbers.splitn(4, ' ');
+                    let mut number = || items.next().and_then(|item| item.parse::<usize>().ok()).ok_or_else(|| invalid(line));
+                    let address = number()? as u32;
+                    let source = SourceInfo {
+                        line: number()?,
+                        column: number()?,
+                        file: items.next().ok_or_else(|| invalid(line))?.to_string(),
+                        text: source_text.to_string(),
+                    };
+                    info.lines.insert(address, source);
+                }
+                "label" => {
+                    let mut items = rest.splitn(3, ' ');
+                    let mut number = || items.next().and_then(|item| item.parse::<u32>().ok()).ok_or_else(|| invalid(line));
+                    let (start, end) = (number()?, number()?);
+                    info.labels.push(LabelRange {
+                        name: items.next().ok_or_else(|| invalid(line))?.to_string(),
+                        start,
+                        end,
+                    });
+                }
+                "variable" => {
+                    let (address, name) = rest.split_once(' ').ok_or_else(|| invalid(line))?;
+                    let address = address.parse().map_err(|_| invalid(line))?;
+                    info.variables.insert(name.to_string(), address);
+                }
+                _ => return Err(invalid(line)),
+            }
+        }
+        Ok(info)
+    }
+
+    /// Write the debug info to a file
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    /// Read debug info from a file
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::from_text(&text).map_err(|msg| io::Error::new(io::ErrorKind::InvalidData, msg))
+    }
+}
@@ -0,0 +1,50 @@
+use crate::constants::DRAM_SIZE;
+use std::fmt::{Display, Formatter};
+
+/// A problem found while running a program that stops the cpu
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// A jump, call or return at `address` tried to move pc to `target`, which is outside of dram
+    JumpOutOfMemory { address: u32, target: u32 },
+    /// A load or store at `address` tried to access dram at `target`, which is outside of dram
+    AccessOutOfMemory { address: u32, target: u32 },
+    /// A push or call at `address` with the stack pointer at `sp`, which has no room left in dram
+    StackOverflow { address: u32, sp: u32 },
+    /// A pop or return at `address` with the stack pointer at `sp`, which is at or below the base of the stack
+    StackUnderflow { address: u32, sp: u32 },
+}
+
+impl Fault {
+    /// Address of the instruction that caused the fault
+    pub fn address(&self) -> u32 {
+        match self {
+            Fault::JumpOutOfMemory { address, .. }
+            | Fault::AccessOutOfMemory { address, .. }
+            | Fault::StackOverflow { address, .. }
+            | Fault::StackUnderflow { address, .. } => *address,
+        }
+    }
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fault::JumpOutOfMemory { address, target } => write!(
+                f,
+                "jump at address {address} to address {target}, which is outside of memory (0..{DRAM_SIZE})"
+            ),
+            Fault::AccessOutOfMemory { address, target } => write!(
+                f,
+                "memory access at address {address} to address {target}, which is outside of memory (0..{DRAM_SIZE})"
+            ),
+            Fault::StackOverflow { address, sp } => write!(
+                f,
+                "stack push at address {address} with sp {sp}, the stack has no room left in memory (0..{DRAM_SIZE})"
+            ),
+            Fault::StackUnderflow { address, sp } => write!(
+                f,
+                "stack pop at address {address} with sp {sp}, the stack has no value left in memory (0..{DRAM_SIZE})"
+            ),
+        }
+    }
+}
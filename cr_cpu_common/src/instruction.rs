@@ -1,13 +1,17 @@
-use std::collections::HashMap;
 use crate::constants::{
-    get_id_from_reg_name, ADD, CMP, DUMP, DUMPR, IADD, IADDL, ICMP, ICMPL, IMOVEL, IPUSH, IPUSHL,
-    ISUB, LEA, LEAR, MOVEA, MOVER, POP, PUSH, SHL, SHR, SUB,
+    get_id_from_reg_name, ADC, ADD, BT, BTC, BTR, BTS, CALL, CALLR, CLZ, CMP, CMPS, CTZ, DEC, DUMP,
+    DUMPR, IADC, IADD, IADDL, IBT, IBTC, IBTR, IBTS, ICMP, ICMPL, ICMPS, IMOVEL, INC, IPUSH,
+    IPUSHL, ISBB, ISUB, JMPR, LDB, LDBS, LDH, LDHS, LEA, LEAO, LEAR, LOOP, MOVEA, MOVEAO, MOVEAR,
+    MOVER, POP, POPCNT, PUSH, RET, ROL, ROLR, ROR, RORR, SAR, SARR, SBB, SHL, SHLR, SHR, SHRR, STB,
+    STH, SUB,
 };
 use crate::instruction::Instruction::{
-    Add, Dump, IAdd, IAddL, ICmp, ICmpL, IMoveL, IPush, IPushL, ISub, Lea, LeaR, MoveA, MoveR, Pop,
-    Push, Shl, Shr, Sub, Unknown, JE, JMP, JOV, JZ,
+    Adc, Add, Bt, Btc, Btr, Bts, Call, CallR, Clz, Cmps, Ctz, Dec, Dump, IAdc, IAdd, IAddL, IBt,
+    IBtc, IBtr, IBts, ICmp, ICmpL, ICmps, IMoveL, IPush, IPushL, ISbb, ISub, Inc, JmpR, Ldb, Ldbs,
+    Ldh, Ldhs, Lea, LeaO, LeaR, Loop, MoveA, MoveAO, MoveAR, MoveR, Pop, Popcnt, Push, Ret, Rol,
+    RolR, Ror, RorR, Sar, SarR, Sbb, Shl, ShlR, Shr, ShrR, Stb, Sth, Sub, Unknown, JC, JE, JGE,
+    JGES, JGTS, JLE, JLES, JLTS, JMP, JNC, JNE, JNO, JNZ, JOV, JZ,
 };
-use crate::PCReference;
 use crate::prelude::{Cmp, JGT, JLT};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -19,6 +23,10 @@ pub enum Instruction {
 
     /// Move register into dram address
     MoveA(u16, u8),
+    /// Move register 1 into the dram address held in register 0
+    MoveAR(u8, u8),
+    /// Move register 2 into the dram address held in register 0 plus a signed offset, `movea [reg + offset] reg`
+    MoveAO(u8, i8, u8),
 
     /// Compare register 0 and register 1, changing flags when necessary
     Cmp(u8, u8),
@@ -26,9 +34,41 @@ pub enum Instruction {
     ICmp(u8, u16),
     /// Compare register 0 and an immediate long number
     ICmpL(u8, u32),
+    /// Compare register 0 and register 1 as signed numbers
+    Cmps(u8, u8),
+    /// Compare register 0 and a signed immediate mode number
+    ICmps(u8, i16),
 
+    /// Shift register 0 left or right by an immediate amount
     Shl(u8, u8),
     Shr(u8, u8),
+    /// Shift register 0 right by an immediate amount, keeping its sign
+    Sar(u8, u8),
+    /// Rotate register 0 left or right by an immediate amount
+    Rol(u8, u8),
+    Ror(u8, u8),
+    /// Shift or rotate register 0 by the amount held in register 1
+    ShlR(u8, u8),
+    ShrR(u8, u8),
+    SarR(u8, u8),
+    RolR(u8, u8),
+    RorR(u8, u8),
+
+    /// Copy the bit of register 0 at the index held in register 1 into carry,
+    /// then leave it, set it, reset it to 0 or complement it
+    Bt(u8, u8),
+    Bts(u8, u8),
+    Btr(u8, u8),
+    Btc(u8, u8),
+    /// Bit tests with an immediate bit index
+    IBt(u8, u8),
+    IBts(u8, u8),
+    IBtr(u8, u8),
+    IBtc(u8, u8),
+    /// Count the set bits, leading zeros or trailing zeros of register 1 into register 0
+    Popcnt(u8, u8),
+    Clz(u8, u8),
+    Ctz(u8, u8),
 
     // TODO: bitwise AND, bitwise OR, bitwise XOR, bitwise NOT instructions
     /// Jump instructions, sets pc to the value given
@@ -38,21 +78,68 @@ pub enum Instruction {
     JLT(u16),
     JZ(u16),
     JOV(u16),
+    /// Signed jump instructions, using the sign and overflow flags so they work after a compare or arithmetic
+    JGTS(u16),
+    JLTS(u16),
+    JGES(u16),
+    JLES(u16),
+    JNE(u16),
+    JNZ(u16),
+    JGE(u16),
+    JLE(u16),
+    JNO(u16),
+    JC(u16),
+    JNC(u16),
+    /// Decrement cr and jump if it is not zero
+    Loop(u16),
+    /// Jump to the address held in a register
+    JmpR(u8),
+
+    /// Push the address of the next instruction, then jump to the address given
+    Call(u16),
+    /// Push the address of the next instruction, then jump to the address held in a register
+    CallR(u8),
+    /// Pop an address pushed by a call into pc
+    Ret,
 
     /// Load effective address into OR
     Lea(u16),
     LeaR(u8),
+    /// Load the dram value at the address held in a register plus a signed offset into OR, `lea [reg + offset]`
+    LeaO(u8, i8),
+    /// Load the byte or half word at the byte address held in register 1 plus a signed offset into register 0,
+    /// zero extended, or sign extended for `Ldbs` and `Ldhs`, `ldb reg [reg + offset]`
+    Ldb(u8, u8, i8),
+    Ldbs(u8, u8, i8),
+    Ldh(u8, u8, i8),
+    Ldhs(u8, u8, i8),
+    /// Store the low byte or half word of register 2 at the byte address held in register 0 plus a signed offset,
+    /// `stb [reg + offset] reg`
+    Stb(u8, i8, u8),
+    Sth(u8, i8, u8),
 
     /// |location unused|number|location unused|opcode|
     IAdd(u8),
     /// Add register 1 into register 0
     Add(u8, u8),
+    /// Add one to a register
+    Inc(u8),
+    /// Subtract one from a register
+    Dec(u8),
     /// Add a long number, uses a modified add opcode that specifies that the number to be added is in the proceeding memory location
     IAddL(u32),
     /// |location unused|number|location unused|opcode|
     ISub(u8),
     /// Subtract register0 from register1
     Sub(u8, u8),
+    /// Add a number and the carry flag to acc, for adding the upper words of a multi-word number
+    IAdc(u8),
+    /// Add register 1 and the carry flag into register 0
+    Adc(u8, u8),
+    /// Subtract a number and the carry flag from acc, for subtracting the upper words of a multi-word number
+    ISbb(u8),
+    /// Subtract register 1 and the carry flag from register 0
+    Sbb(u8, u8),
     /// Push number to stack
     IPush(u16),
     Push(u8),
@@ -132,6 +219,22 @@ impl Instruction {
                 let inst: u32 = SUB as u32 | (*reg0 as u32) << 8 | (*reg1 as u32) << 16;
                 vec![inst]
             }
+            IAdc(number) => {
+                let inst: u32 = IADC as u32 | (*number as u32) << 16;
+                vec![inst]
+            }
+            Adc(reg0, reg1) => {
+                let inst: u32 = ADC as u32 | (*reg0 as u32) << 8 | (*reg1 as u32) << 16;
+                vec![inst]
+            }
+            ISbb(number) => {
+                let inst: u32 = ISBB as u32 | (*number as u32) << 16;
+                vec![inst]
+            }
+            Sbb(reg0, reg1) => {
+                let inst: u32 = SBB as u32 | (*reg0 as u32) << 8 | (*reg1 as u32) << 16;
+                vec![inst]
+            }
             JOV(pc) => {
                 let inst: u32 = crate::constants::JOV as u32 | ((*pc as u32) << 8);
                 vec![inst]
@@ -148,6 +251,83 @@ impl Instruction {
                 let inst: u32 = ICMPL as u32 | (*reg0 as u32) << 8;
                 vec![inst, *val]
             }
+            Cmps(reg0, reg1) => {
+                let inst: u32 = CMPS as u32 | (*reg0 as u32) << 8 | (*reg1 as u32) << 16;
+                vec![inst]
+            }
+            ICmps(reg0, val) => {
+                let inst: u32 = ICMPS as u32 | (*reg0 as u32) << 8 | (*val as u16 as u32) << 16;
+                vec![inst]
+            }
+            JGTS(pc) => {
+                let inst: u32 = crate::constants::JGTS as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            JLTS(pc) => {
+                let inst: u32 = crate::constants::JLTS as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            JGES(pc) => {
+                let inst: u32 = crate::constants::JGES as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            JLES(pc) => {
+                let inst: u32 = crate::constants::JLES as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            JNE(pc) => {
+                let inst: u32 = crate::constants::JNE as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            JNZ(pc) => {
+                let inst: u32 = crate::constants::JNZ as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            JGE(pc) => {
+                let inst: u32 = crate::constants::JGE as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            JLE(pc) => {
+                let inst: u32 = crate::constants::JLE as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            JNO(pc) => {
+                let inst: u32 = crate::constants::JNO as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            JC(pc) => {
+                let inst: u32 = crate::constants::JC as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            JNC(pc) => {
+                let inst: u32 = crate::constants::JNC as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            Loop(pc) => {
+                let inst: u32 = LOOP as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            Inc(reg0) => {
+                let inst: u32 = INC as u32 | (*reg0 as u32) << 8;
+                vec![inst]
+            }
+            Dec(reg0) => {
+                let inst: u32 = DEC as u32 | (*reg0 as u32) << 8;
+                vec![inst]
+            }
+            JmpR(reg0) => {
+                let inst: u32 = JMPR as u32 | (*reg0 as u32) << 8;
+                vec![inst]
+            }
+            Call(pc) => {
+                let inst: u32 = CALL as u32 | ((*pc as u32) << 8);
+                vec![inst]
+            }
+            CallR(reg0) => {
+                let inst: u32 = CALLR as u32 | (*reg0 as u32) << 8;
+                vec![inst]
+            }
+            Ret => vec![RET as u32],
             IPushL(number) => {
                 let inst: u32 = IPUSHL as u32;
                 vec![inst, *number]
@@ -172,17 +352,79 @@ impl Instruction {
                 let inst: u32 = LEAR as u32 | (*reg as u32) << 8;
                 vec![inst]
             }
+            LeaO(reg, offset) => {
+                let inst: u32 = LEAO as u32 | (*reg as u32) << 8 | (*offset as u8 as u32) << 16;
+                vec![inst]
+            }
+            Ldb(reg0, base_reg, offset) => {
+                let inst: u32 =
+                    LDB as u32 | (*reg0 as u32) << 8 | (*base_reg as u32) << 16 | (*offset as u8 as u32) << 24;
+                vec![inst]
+            }
+            Ldbs(reg0, base_reg, offset) => {
+                let inst: u32 =
+                    LDBS as u32 | (*reg0 as u32) << 8 | (*base_reg as u32) << 16 | (*offset as u8 as u32) << 24;
+                vec![inst]
+            }
+            Ldh(reg0, base_reg, offset) => {
+                let inst: u32 =
+                    LDH as u32 | (*reg0 as u32) << 8 | (*base_reg as u32) << 16 | (*offset as u8 as u32) << 24;
+                vec![inst]
+            }
+            Ldhs(reg0, base_reg, offset) => {
+                let inst: u32 =
+                    LDHS as u32 | (*reg0 as u32) << 8 | (*base_reg as u32) << 16 | (*offset as u8 as u32) << 24;
+                vec![inst]
+            }
+            Stb(base_reg, offset, reg0) => {
+                let inst: u32 =
+                    STB as u32 | (*base_reg as u32) << 8 | (*offset as u8 as u32) << 16 | (*reg0 as u32) << 24;
+                vec![inst]
+            }
+            Sth(base_reg, offset, reg0) => {
+                let inst: u32 =
+                    STH as u32 | (*base_reg as u32) << 8 | (*offset as u8 as u32) << 16 | (*reg0 as u32) << 24;
+                vec![inst]
+            }
+            MoveAR(address_reg, reg0) => {
+                let inst: u32 = MOVEAR as u32 | (*address_reg as u32) << 8 | (*reg0 as u32) << 16;
+                vec![inst]
+            }
+            MoveAO(base_reg, offset, reg0) => {
+                let inst: u32 =
+                    MOVEAO as u32 | (*base_reg as u32) << 8 | (*offset as u8 as u32) << 16 | (*reg0 as u32) << 24;
+                vec![inst]
+            }
             Instruction::Shl(reg, amnt) => {
                 vec![(SHL as u32 | (*reg as u32) << 8) | (*amnt as u32) << 16]
             }
             Instruction::Shr(reg, amnt) => {
                 vec![(SHR as u32 | (*reg as u32) << 8) | (*amnt as u32) << 16]
             }
+            Sar(reg, amnt) => vec![(SAR as u32 | (*reg as u32) << 8) | (*amnt as u32) << 16],
+            Rol(reg, amnt) => vec![(ROL as u32 | (*reg as u32) << 8) | (*amnt as u32) << 16],
+            Ror(reg, amnt) => vec![(ROR as u32 | (*reg as u32) << 8) | (*amnt as u32) << 16],
+            ShlR(reg, amnt_reg) => vec![(SHLR as u32 | (*reg as u32) << 8) | (*amnt_reg as u32) << 16],
+            ShrR(reg, amnt_reg) => vec![(SHRR as u32 | (*reg as u32) << 8) | (*amnt_reg as u32) << 16],
+            SarR(reg, amnt_reg) => vec![(SARR as u32 | (*reg as u32) << 8) | (*amnt_reg as u32) << 16],
+            RolR(reg, amnt_reg) => vec![(ROLR as u32 | (*reg as u32) << 8) | (*amnt_reg as u32) << 16],
+            RorR(reg, amnt_reg) => vec![(RORR as u32 | (*reg as u32) << 8) | (*amnt_reg as u32) << 16],
+            Bt(reg, index_reg) => vec![(BT as u32 | (*reg as u32) << 8) | (*index_reg as u32) << 16],
+            Bts(reg, index_reg) => vec![(BTS as u32 | (*reg as u32) << 8) | (*index_reg as u32) << 16],
+            Btr(reg, index_reg) => vec![(BTR as u32 | (*reg as u32) << 8) | (*index_reg as u32) << 16],
+            Btc(reg, index_reg) => vec![(BTC as u32 | (*reg as u32) << 8) | (*index_reg as u32) << 16],
+            IBt(reg, index) => vec![(IBT as u32 | (*reg as u32) << 8) | (*index as u32) << 16],
+            IBts(reg, index) => vec![(IBTS as u32 | (*reg as u32) << 8) | (*index as u32) << 16],
+            IBtr(reg, index) => vec![(IBTR as u32 | (*reg as u32) << 8) | (*index as u32) << 16],
+            IBtc(reg, index) => vec![(IBTC as u32 | (*reg as u32) << 8) | (*index as u32) << 16],
+            Popcnt(reg0, reg1) => vec![(POPCNT as u32 | (*reg0 as u32) << 8) | (*reg1 as u32) << 16],
+            Clz(reg0, reg1) => vec![(CLZ as u32 | (*reg0 as u32) << 8) | (*reg1 as u32) << 16],
+            Ctz(reg0, reg1) => vec![(CTZ as u32 | (*reg0 as u32) << 8) | (*reg1 as u32) << 16],
         }
     }
 
-    pub fn from_code_line(line: &Vec<String>, added_lines: u32, map: &HashMap<String, PCReference>) -> Option<Self> {
-        let uncap_line = line.get(0).unwrap().to_lowercase();
+    pub fn from_code_line(line: &[String]) -> Option<Self> {
+        let uncap_line = line.first()?.to_lowercase();
         match uncap_line.as_str() {
             // TODO: use https://crates.io/crates/eval eval crate here when parsing numbers so we can allow for expressions
             "add" => {
@@ -207,21 +449,16 @@ impl Instruction {
                     return Some(Instruction::DumpR(reg0id));
                 }
             }
-            "move" => {
-                if line.len() == 3 {
-                    let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
-                    let reg1id = get_id_from_reg_name(line.get(2)?)?;
-                    return Some(MoveR(reg0id, reg1id));
-                }
+            "move" if line.len() == 3 => {
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                let reg1id = get_id_from_reg_name(line.get(2)?)?;
+                return Some(MoveR(reg0id, reg1id));
             }
-            "imovel" => {
-                // immediate move long
-                if line.len() == 3 {
-                    let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
-                    let num = line.get(2)?.parse().ok()?;
-                    assert_ne!(num, 0, "imovel does not support 0 values");
-                    return Some(IMoveL(reg0id, num));
-                }
+            // immediate move long
+            "imovel" if line.len() == 3 => {
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                let num = parse_long(line.get(2)?)?;
+                return Some(IMoveL(reg0id, num));
             }
             "sub" => {
                 // sub reg
@@ -236,125 +473,239 @@ impl Instruction {
                     return Some(ISub(line.get(1)?.parse().ok()?));
                 }
             }
-            "jov" => {
+            "adc" => {
+                // immediate add with carry
                 if line.len() == 2 {
-                    return Some(JOV(
-                        (line.get(1)?.parse::<u32>().ok()? + added_lines - 1) as u16
-                    ));
+                    return Some(IAdc(line.get(1)?.parse().ok()?));
                 }
-            }
-            "jz" => {
-                if line.len() == 2 {
-                    return Some(JZ(
-                        (line.get(1)?.parse::<u32>().ok()? + added_lines - 1) as u16
-                    ));
+                // add with carry r
+                if line.len() == 3 {
+                    let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                    let reg1id: u8 = get_id_from_reg_name(line.get(2)?)?;
+
+                    return Some(Adc(reg0id, reg1id));
                 }
             }
-            "jgt" => {
-                if line.len() == 2 {
-                    return Some(JGT(
-                        (line.get(1)?.parse::<u32>().ok()? + added_lines - 1) as u16
-                    ));
+            "sbb" => {
+                // sub with borrow reg
+                if line.len() == 3 {
+                    let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                    let reg1id: u8 = get_id_from_reg_name(line.get(2)?)?;
+
+                    return Some(Sbb(reg0id, reg1id));
                 }
-            }
-            "jlt" => {
+                // immediate sub with borrow
                 if line.len() == 2 {
-                    return Some(JLT(
-                        (line.get(1)?.parse::<u32>().ok()? + added_lines - 1) as u16
-                    ));
+                    return Some(ISbb(line.get(1)?.parse().ok()?));
                 }
             }
-            "je" => {
-                if line.len() == 2 {
-                    return Some(JE(
-                        (line.get(1)?.parse::<u32>().ok()? + added_lines - 1) as u16
-                    ));
+            "jov" if line.len() == 2 => {
+                return Some(JOV(line.get(1)?.parse().ok()?));
+            }
+            "jz" if line.len() == 2 => {
+                return Some(JZ(line.get(1)?.parse().ok()?));
+            }
+            "jgt" if line.len() == 2 => {
+                return Some(JGT(line.get(1)?.parse().ok()?));
+            }
+            "jlt" if line.len() == 2 => {
+                return Some(JLT(line.get(1)?.parse().ok()?));
+            }
+            "jgts" if line.len() == 2 => {
+                return Some(JGTS(line.get(1)?.parse().ok()?));
+            }
+            "jlts" if line.len() == 2 => {
+                return Some(JLTS(line.get(1)?.parse().ok()?));
+            }
+            "jges" if line.len() == 2 => {
+                return Some(JGES(line.get(1)?.parse().ok()?));
+            }
+            "jles" if line.len() == 2 => {
+                return Some(JLES(line.get(1)?.parse().ok()?));
+            }
+            "jne" if line.len() == 2 => {
+                return Some(JNE(line.get(1)?.parse().ok()?));
+            }
+            "jnz" if line.len() == 2 => {
+                return Some(JNZ(line.get(1)?.parse().ok()?));
+            }
+            "jge" if line.len() == 2 => {
+                return Some(JGE(line.get(1)?.parse().ok()?));
+            }
+            "jle" if line.len() == 2 => {
+                return Some(JLE(line.get(1)?.parse().ok()?));
+            }
+            "jno" if line.len() == 2 => {
+                return Some(JNO(line.get(1)?.parse().ok()?));
+            }
+            "jc" if line.len() == 2 => {
+                return Some(JC(line.get(1)?.parse().ok()?));
+            }
+            "jnc" if line.len() == 2 => {
+                return Some(JNC(line.get(1)?.parse().ok()?));
+            }
+            "loop" if line.len() == 2 => {
+                return Some(Loop(line.get(1)?.parse().ok()?));
+            }
+            "inc" if line.len() == 2 => {
+                return Some(Inc(get_id_from_reg_name(line.get(1)?)?));
+            }
+            "dec" if line.len() == 2 => {
+                return Some(Dec(get_id_from_reg_name(line.get(1)?)?));
+            }
+            "je" if line.len() == 2 => {
+                return Some(JE(line.get(1)?.parse().ok()?));
+            }
+            "jmp" if line.len() == 2 => {
+                if let Ok(pc) = line.get(1)?.parse() {
+                    return Some(JMP(pc));
                 }
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                return Some(JmpR(reg0id));
             }
-            "jmp" => {
-                if line.len() == 2 {
-                    return Some(JMP(
-                        (line.get(1)?.parse::<u32>().ok()? + added_lines - 1) as u16
-                    ));
+            "call" if line.len() == 2 => {
+                if let Ok(pc) = line.get(1)?.parse() {
+                    return Some(Call(pc));
                 }
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                return Some(CallR(reg0id));
             }
-            "cmp" => {
-                if line.len() == 3 {
-                    let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
-                    let reg1id: u8 = get_id_from_reg_name(line.get(2)?)?;
+            "ret" if line.len() == 1 => {
+                return Some(Ret);
+            }
+            "cmp" if line.len() == 3 => {
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                let reg1id: u8 = get_id_from_reg_name(line.get(2)?)?;
 
-                    return Some(Cmp(reg0id, reg1id));
-                }
+                return Some(Cmp(reg0id, reg1id));
             }
-            "icmp" => {
-                if line.len() == 3 {
-                    let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
-                    let val: u16 = line.get(2)?.parse().ok()?;
-                    return Some(ICmp(reg0id, val));
-                }
+            "icmp" if line.len() == 3 => {
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                let val: u16 = line.get(2)?.parse().ok()?;
+                return Some(ICmp(reg0id, val));
             }
-            "icmpl" => {
-                if line.len() == 3 {
-                    let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
-                    let val: u32 = line.get(2)?.parse().ok()?;
+            "icmpl" if line.len() == 3 => {
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                let val: u32 = parse_long(line.get(2)?)?;
 
-                    return Some(ICmpL(reg0id, val));
-                }
+                return Some(ICmpL(reg0id, val));
             }
-            "push" => {
-                if line.len() == 2 {
-                    if let Ok(literal_num) = line.get(1)?.parse::<u16>() {
-                        return Some(IPush(literal_num));
-                    }
-                    let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
-                    return Some(Push(reg0id));
-                    // let val: u32 = line.get(1)?.parse().ok()?;
-                    // return Some(IPush(val as u16));
-                }
+            "cmps" if line.len() == 3 => {
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                let reg1id: u8 = get_id_from_reg_name(line.get(2)?)?;
+
+                return Some(Cmps(reg0id, reg1id));
             }
-            "ipushl" => {
-                if line.len() == 2 {
-                    let val: u32 = line.get(1)?.parse().ok()?;
-                    return Some(IPushL(val));
-                }
+            "icmps" if line.len() == 3 => {
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                let val: i16 = line.get(2)?.parse().ok()?;
+                return Some(ICmps(reg0id, val));
             }
-            "pop" => {
-                if line.len() == 1 {
-                    return Some(Pop);
+            "push" if line.len() == 2 => {
+                if let Ok(literal_num) = line.get(1)?.parse::<u16>() {
+                    return Some(IPush(literal_num));
                 }
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                return Some(Push(reg0id));
+                // let val: u32 = line.get(1)?.parse().ok()?;
+                // return Some(IPush(val as u16));
             }
-            "lea" => {
-                if line.len() == 2 {
-                    return if let Ok(number) = line.get(1)?.parse::<u16>() {
-                        Some(Lea(number))
-                    } else {
-                        let var_pc = map.get(line.get(1)?)?;
-                        Some(Lea(var_pc.0 as u16))
-                    }
-                }
+            "ipushl" if line.len() == 2 => {
+                let val: u32 = line.get(1)?.parse().ok()?;
+                return Some(IPushL(val));
             }
-            "movea" => {
-                if line.len() == 3 {
-                    let reg0id: u8 = get_id_from_reg_name(line.get(2)?)?;
-                    return if let Ok(number) = line.get(1)?.parse::<u16>() {
-                        Some(MoveA(number, reg0id))
-                    } else {
-                        let var_pc = map.get(line.get(1)?)?;
-                        Some(MoveA(var_pc.0 as u16, reg0id))
-                    }
-                }
+            "pop" if line.len() == 1 => {
+                return Some(Pop);
             }
-            "shr" => {
-                if line.len() == 3 {
-                    let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
-                    return Some(Shr(reg0id, line.get(2)?.parse().ok()?));
+            "lea" if line.len() == 2 => {
+                if let Ok(address) = line.get(1)?.parse() {
+                    return Some(Lea(address));
+                }
+                if let Some((base, offset)) = parse_memory_operand(line.get(1)?) {
+                    return Some(LeaO(base, offset));
                 }
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                return Some(LeaR(reg0id));
             }
-            "shl" => {
-                if line.len() == 3 {
-                    let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
-                    return Some(Shl(reg0id, line.get(2)?.parse().ok()?));
+            "movea" if line.len() == 3 => {
+                let reg0id: u8 = get_id_from_reg_name(line.get(2)?)?;
+                if let Some((base, offset)) = parse_memory_operand(line.get(1)?) {
+                    return Some(MoveAO(base, offset, reg0id));
                 }
+                return Some(MoveA(line.get(1)?.parse().ok()?, reg0id));
+            }
+            // byte and half word loads and stores address bytes, through a memory operand such as [acc + 2]
+            "ldb" | "ldbs" | "ldh" | "ldhs" if line.len() == 3 => {
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                let (base, offset) = parse_memory_operand(line.get(2)?)?;
+                return Some(match uncap_line.as_str() {
+                    "ldb" => Ldb(reg0id, base, offset),
+                    "ldbs" => Ldbs(reg0id, base, offset),
+                    "ldh" => Ldh(reg0id, base, offset),
+                    _ => Ldhs(reg0id, base, offset),
+                });
+            }
+            "stb" | "sth" if line.len() == 3 => {
+                let (base, offset) = parse_memory_operand(line.get(1)?)?;
+                let reg0id: u8 = get_id_from_reg_name(line.get(2)?)?;
+                return Some(match uncap_line.as_str() {
+                    "stb" => Stb(base, offset, reg0id),
+                    _ => Sth(base, offset, reg0id),
+                });
+            }
+            "movear" if line.len() == 3 => {
+                let address_reg: u8 = get_id_from_reg_name(line.get(1)?)?;
+                let reg0id: u8 = get_id_from_reg_name(line.get(2)?)?;
+                return Some(MoveAR(address_reg, reg0id));
+            }
+            // bit tests take an immediate bit index, or a register holding the index
+            "bt" | "bts" | "btr" | "btc" if line.len() == 3 => {
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                if let Ok(index) = line.get(2)?.parse() {
+                    return Some(match uncap_line.as_str() {
+                        "bt" => IBt(reg0id, index),
+                        "bts" => IBts(reg0id, index),
+                        "btr" => IBtr(reg0id, index),
+                        _ => IBtc(reg0id, index),
+                    });
+                }
+                let reg1id: u8 = get_id_from_reg_name(line.get(2)?)?;
+                return Some(match uncap_line.as_str() {
+                    "bt" => Bt(reg0id, reg1id),
+                    "bts" => Bts(reg0id, reg1id),
+                    "btr" => Btr(reg0id, reg1id),
+                    _ => Btc(reg0id, reg1id),
+                });
+            }
+            "popcnt" | "clz" | "ctz" if line.len() == 3 => {
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                let reg1id: u8 = get_id_from_reg_name(line.get(2)?)?;
+                return Some(match uncap_line.as_str() {
+                    "popcnt" => Popcnt(reg0id, reg1id),
+                    "clz" => Clz(reg0id, reg1id),
+                    _ => Ctz(reg0id, reg1id),
+                });
+            }
+            // shifts and rotates take an immediate amount, or a register holding the amount
+            "shl" | "shr" | "sar" | "rol" | "ror" if line.len() == 3 => {
+                let reg0id: u8 = get_id_from_reg_name(line.get(1)?)?;
+                if let Ok(amount) = line.get(2)?.parse() {
+                    return Some(match uncap_line.as_str() {
+                        "shl" => Shl(reg0id, amount),
+                        "shr" => Shr(reg0id, amount),
+                        "sar" => Sar(reg0id, amount),
+                        "rol" => Rol(reg0id, amount),
+                        _ => Ror(reg0id, amount),
+                    });
+                }
+                let reg1id: u8 = get_id_from_reg_name(line.get(2)?)?;
+                return Some(match uncap_line.as_str() {
+                    "shl" => ShlR(reg0id, reg1id),
+                    "shr" => ShrR(reg0id, reg1id),
+                    "sar" => SarR(reg0id, reg1id),
+                    "rol" => RolR(reg0id, reg1id),
+                    _ => RorR(reg0id, reg1id),
+                });
             }
             _ => {}
         }
@@ -369,26 +720,143 @@ impl Instruction {
         None
     }
 
-    pub fn change_jump_line(&mut self, pc: u16) {
+    /// The address a jump instruction jumps to, or None if the instruction is not a jump
+    pub fn jump_target(&self) -> Option<u16> {
+        match self {
+            JMP(target) | JE(target) | JGT(target) | JLT(target) | JZ(target) | JOV(target)
+            | JGTS(target) | JLTS(target) | JGES(target) | JLES(target) | JNE(target) | JNZ(target)
+            | JGE(target) | JLE(target) | JNO(target) | JC(target) | JNC(target) | Loop(target) => Some(*target),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the instruction has an operand that is an address or immediate number,
+    /// which the assembler can fill in using a label or variable
+    pub fn has_address_operand(&self) -> bool {
+        self.clone().set_address_operand(0).is_some()
+    }
+
+    /// Set the address or immediate number operand of the instruction,
+    /// returns None if the instruction has no such operand or the value does not fit in it
+    pub fn set_address_operand(&mut self, value: u32) -> Option<()> {
         match self {
-            JMP(line_num) | JE(line_num) | JGT(line_num) | JLT(line_num) | JZ(line_num)
-            | JOV(line_num) => {
-                *line_num = pc;
+            JMP(operand) | JE(operand) | JGT(operand) | JLT(operand) | JZ(operand)
+            | JOV(operand) | JGTS(operand) | JLTS(operand) | JGES(operand) | JLES(operand) | JNE(operand)
+            | JNZ(operand) | JGE(operand) | JLE(operand) | JNO(operand) | JC(operand) | JNC(operand) | Loop(operand)
+            | Call(operand) | Lea(operand) | MoveA(operand, _) | IPush(operand)
+            | ICmp(_, operand) => {
+                *operand = u16::try_from(value).ok()?;
+            }
+            IMoveL(_, operand) | ICmpL(_, operand) | IPushL(operand) | IAddL(operand) => {
+                *operand = value;
             }
             _ => {
-                panic!("Unexpected jump instruction, this should pretty much never happen");
+                return None;
             }
         }
+        Some(())
     }
 
-    pub fn change_lea(&mut self, pc: u16) {
+    /// Where the address or immediate number operand is stored in the instruction data,
+    /// as the index of the word holding it, the bit shift of the operand in that word, and the width of the operand in bits
+    pub fn address_operand_position(&self) -> Option<(usize, u32, u32)> {
         match self {
-            Lea(a) => {
-                *a = pc;
-            }
-            _ => {
-                panic!("Unexpected Lea");
+            JMP(_) | JE(_) | JGT(_) | JLT(_) | JZ(_) | JOV(_) | JGTS(_) | JLTS(_) | JGES(_) | JLES(_) | JNE(_)
+            | JNZ(_) | JGE(_) | JLE(_) | JNO(_) | JC(_) | JNC(_) | Loop(_) | Call(_) | Lea(_) | MoveA(_, _) | IPush(_) => {
+                Some((0, 8, 16))
             }
+            ICmp(_, _) => Some((0, 16, 16)),
+            IMoveL(_, _) | ICmpL(_, _) | IPushL(_) | IAddL(_) => Some((1, 0, 32)),
+            _ => None,
         }
     }
 }
+
+/// Parse a 32 bit number, negative numbers are stored as two's complement, e.g. `-1` is `0xFFFFFFFF`
+fn parse_long(text: &str) -> Option<u32> {
+    text.parse::<u32>().ok().or_else(|| text.parse::<i32>().ok().map(|value| value as u32))
+}
+
+/// Parse a memory operand of a register and an optional signed offset, e.g. `[acc]`, `[acc + 4]` or `[sp - 1]`
+fn parse_memory_operand(text: &str) -> Option<(u8, i8)> {
+    let inner: String = text.strip_prefix('[')?.strip_suffix(']')?.split_whitespace().collect();
+    let split = inner.find(['+', '-']).unwrap_or(inner.len());
+    let (reg, offset) = inner.split_at(split);
+    let offset = match offset.strip_prefix('+') {
+        _ if offset.is_empty() => 0,
+        Some(positive) => positive.parse().ok()?,
+        None => offset.parse().ok()?,
+    };
+    Some((get_id_from_reg_name(reg)?, offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{ACC, CR, R0};
+    use crate::cpu::Cpu;
+
+    /// Parse a line of assembly, check it gives the expected instruction,
+    /// then check that encoding it and decoding it again on the cpu gives the same instruction back
+    fn assert_round_trip(text: &str, expected: Instruction) {
+        let line: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+        assert_eq!(Instruction::from_code_line(&line), Some(expected.clone()), "parsing {text}");
+
+        let mut cpu = Cpu::new();
+        cpu.write_dram(0, &expected.to_instruction_data());
+        assert_eq!(cpu.fetch_at(0), expected, "decoding {text}");
+    }
+
+    #[test]
+    fn bit_instructions_round_trip() {
+        assert_round_trip("bt acc cr", Bt(ACC, CR));
+        assert_round_trip("bts r3 acc", Bts(R0 + 3, ACC));
+        assert_round_trip("btr acc r15", Btr(ACC, R0 + 15));
+        assert_round_trip("btc cr acc", Btc(CR, ACC));
+        assert_round_trip("bt acc 31", IBt(ACC, 31));
+        assert_round_trip("bts acc 0", IBts(ACC, 0));
+        assert_round_trip("btr r1 7", IBtr(R0 + 1, 7));
+        assert_round_trip("btc cr 255", IBtc(CR, 255));
+        assert_round_trip("popcnt acc cr", Popcnt(ACC, CR));
+        assert_round_trip("clz r0 r1", Clz(R0, R0 + 1));
+        assert_round_trip("ctz cr acc", Ctz(CR, ACC));
+    }
+
+    #[test]
+    fn carry_instructions_round_trip() {
+        assert_round_trip("adc acc cr", Adc(ACC, CR));
+        assert_round_trip("sbb r2 r3", Sbb(R0 + 2, R0 + 3));
+        assert_round_trip("adc 200", IAdc(200));
+        assert_round_trip("sbb 1", ISbb(1));
+    }
+
+    #[test]
+    fn signed_compares_and_jumps_round_trip() {
+        assert_round_trip("cmps acc cr", Cmps(ACC, CR));
+        assert_round_trip("icmps acc -5", ICmps(ACC, -5));
+        assert_round_trip("icmps acc 32767", ICmps(ACC, i16::MAX));
+        assert_round_trip("jgts 12", JGTS(12));
+        assert_round_trip("jlts 0", JLTS(0));
+        assert_round_trip("jges 127", JGES(127));
+        assert_round_trip("jles 65535", JLES(u16::MAX));
+    }
+
+    #[test]
+    fn other_conditional_jumps_round_trip() {
+        assert_round_trip("jne 1", JNE(1));
+        assert_round_trip("jnz 2", JNZ(2));
+        assert_round_trip("jge 3", JGE(3));
+        assert_round_trip("jle 4", JLE(4));
+        assert_round_trip("jno 5", JNO(5));
+        assert_round_trip("jc 6", JC(6));
+        assert_round_trip("jnc 7", JNC(7));
+    }
+
+    #[test]
+    fn bit_instructions_need_a_register_to_test() {
+        let line: Vec<String> = ["bt", "3", "acc"].map(str::to_string).to_vec();
+        assert_eq!(Instruction::from_code_line(&line), None);
+        let line: Vec<String> = ["popcnt", "acc", "3"].map(str::to_string).to_vec();
+        assert_eq!(Instruction::from_code_line(&line), None);
+    }
+}
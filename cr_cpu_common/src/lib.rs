@@ -1,5 +1,7 @@
 pub mod constants;
 pub mod cpu;
+pub mod debug_info;
+pub mod fault;
 pub mod instruction;
 
 pub mod prelude {